@@ -3,6 +3,7 @@
 // part2: What eight capital letters appear on your CRT?
 
 use crate::error::Error;
+use crate::utils::render_grid;
 use std::fmt::{Debug, Formatter};
 use std::str::FromStr;
 
@@ -25,10 +26,14 @@ impl Debug for States {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct State {
     pub cycle: usize,
+    // address of the instruction being executed
+    pub pc: usize,
+    // value of x during the cycle
     pub x: i64,
+    // applied to x at the end of the cycle
     pub change: i64,
     pub instruction: Instruction,
 }
@@ -39,88 +44,337 @@ impl State {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Noop(),
     AddX(i64),
+    MulX(i64),
+    // relative to the jump itself
+    Jmp(i64),
+    // skips the next instruction if x is zero
+    SkipZ(),
+    // skips the next instruction if x is not zero
+    SkipNz(),
+}
+
+// builds an instruction from its operand, None if the operand is missing or not expected
+pub type Build = fn(Option<i64>) -> Option<Instruction>;
+
+// mnemonic, cycle count and constructor of every instruction, indexed by `Instruction::opcode`
+pub const INSTRUCTION_SET: [(&str, usize, Build); 6] = [
+    ("noop", 1, |operand| operand.is_none().then_some(Instruction::Noop())),
+    ("addx", 2, |operand| operand.map(Instruction::AddX)),
+    ("mulx", 4, |operand| operand.map(Instruction::MulX)),
+    ("jmp", 2, |operand| operand.map(Instruction::Jmp)),
+    ("skipz", 1, |operand| operand.is_none().then_some(Instruction::SkipZ())),
+    ("skipnz", 1, |operand| operand.is_none().then_some(Instruction::SkipNz())),
+];
+
+impl Instruction {
+    fn opcode(&self) -> usize {
+        match self {
+            Instruction::Noop() => 0,
+            Instruction::AddX(_) => 1,
+            Instruction::MulX(_) => 2,
+            Instruction::Jmp(_) => 3,
+            Instruction::SkipZ() => 4,
+            Instruction::SkipNz() => 5,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        INSTRUCTION_SET[self.opcode()].0
+    }
+
+    pub fn cycles(&self) -> usize {
+        INSTRUCTION_SET[self.opcode()].1
+    }
 }
 
 impl FromStr for Instruction {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.starts_with("noop") {
-            Ok(Instruction::Noop())
-        } else if s.starts_with("addx") {
-            Ok(Instruction::AddX(s[5..].parse()?))
-        } else {
-            Err(Error::General(format!("invalid instruction: {}", s)))
+        let invalid = || Error::General(format!("invalid instruction: {}", s));
+        let mut parts = s.split_whitespace();
+        let mnemonic = parts.next().ok_or_else(invalid)?;
+        let (_, _, build) = INSTRUCTION_SET.iter().find(|(m, _, _)| *m == mnemonic).ok_or_else(invalid)?;
+        let operand = parts.next().map(|operand| operand.parse::<i64>()).transpose()?;
+        if parts.next().is_some() {
+            return Err(invalid());
         }
+        build(operand).ok_or_else(invalid)
     }
 }
 
-pub fn execute(input: &str) -> Result<States, Error> {
-    let mut states: Vec<State> = vec![State {
-        cycle: 0,
-        x: 1,
-        change: 0,
-        instruction: Instruction::Noop(),
-    }];
-    for instruction in input.trim_start().trim_end().lines() {
-        let instruction = instruction.parse::<Instruction>()?;
+pub fn load_program(input: &str) -> Result<Vec<Instruction>, Error> {
+    input.trim_start().trim_end().lines().map(|line| line.trim().parse()).collect()
+}
+
+// instructions take effect at the end of their last cycle
+#[derive(Debug)]
+pub struct Cpu {
+    pub program: Vec<Instruction>,
+    pub pc: usize,
+    pub x: i64,
+    // number of completed cycles
+    pub cycle: usize,
+    remaining: usize,
+}
+
+impl Cpu {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self {
+            program,
+            pc: 0,
+            x: 1,
+            cycle: 0,
+            remaining: 0,
+        }
+    }
+
+    // true when the next cycle starts a new instruction
+    pub fn fetching(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn halted(&self) -> bool {
+        self.fetching() && self.pc >= self.program.len()
+    }
+
+    // runs one cycle, or returns None once the program has finished
+    pub fn step(&mut self) -> Result<Option<State>, Error> {
+        if self.halted() {
+            return Ok(None);
+        }
+        if self.fetching() {
+            self.remaining = self.program[self.pc].cycles();
+        }
+        let instruction = self.program[self.pc];
+        let pc = self.pc;
+        let x = self.x;
+        self.cycle += 1;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.retire(instruction)?;
+        }
+        Ok(Some(State {
+            cycle: self.cycle,
+            pc,
+            x,
+            change: self.x - x,
+            instruction,
+        }))
+    }
+
+    fn retire(&mut self, instruction: Instruction) -> Result<(), Error> {
+        let overflow = || Error::General(format!("overflow in cycle {}: {:?}", self.cycle, instruction));
+        let mut next = self.pc as i64 + 1;
         match instruction {
-            Instruction::Noop() => {
-                let last = states.last().unwrap();
-                states.push(State {
-                    cycle: last.cycle + 1,
-                    x: last.x + last.change,
-                    change: 0,
-                    instruction,
-                })
+            Instruction::Noop() => {}
+            Instruction::AddX(amount) => self.x = self.x.checked_add(amount).ok_or_else(overflow)?,
+            Instruction::MulX(amount) => self.x = self.x.checked_mul(amount).ok_or_else(overflow)?,
+            Instruction::Jmp(offset) => next = self.pc as i64 + offset,
+            Instruction::SkipZ() if self.x == 0 => next += 1,
+            Instruction::SkipNz() if self.x != 0 => next += 1,
+            Instruction::SkipZ() | Instruction::SkipNz() => {}
+        }
+        if next < 0 {
+            return Err(Error::General(format!("jump to {} in cycle {}", next, self.cycle)));
+        }
+        self.pc = next as usize;
+        Ok(())
+    }
+
+    // runs until the given cycle has been executed and returns its state
+    pub fn run_to_cycle(&mut self, cycle: usize) -> Result<Option<State>, Error> {
+        while let Some(state) = self.step()? {
+            if state.cycle >= cycle {
+                return Ok(Some(state));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    // stops after the given cycle
+    Cycle(usize),
+    // stops after the first cycle of the instruction at the given address
+    Pc(usize),
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Step(State),
+    Breakpoint(Breakpoint, State),
+    // x changed at the end of this cycle
+    Watch(State),
+    Halted,
+}
+
+// with jmp a program can loop forever, so execute and Debugger::run give up after this many cycles
+pub const MAX_CYCLES: usize = 1_000_000;
+
+pub struct Debugger {
+    pub cpu: Cpu,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watch_x: bool,
+    // the most cycles a single call to run executes
+    pub max_cycles: usize,
+}
+
+impl Debugger {
+    pub fn new(cpu: Cpu) -> Self {
+        Self {
+            cpu,
+            breakpoints: Vec::new(),
+            watch_x: false,
+            max_cycles: MAX_CYCLES,
+        }
+    }
+
+    // runs one cycle and reports every breakpoint and watch it triggered, or a single Step or Halted if there were none
+    pub fn step(&mut self) -> Result<Vec<Event>, Error> {
+        let fetching = self.cpu.fetching();
+        let state = match self.cpu.step()? {
+            Some(state) => state,
+            None => return Ok(vec![Event::Halted]),
+        };
+        let mut events = Vec::new();
+        for &breakpoint in self.breakpoints.iter() {
+            let hit = match breakpoint {
+                Breakpoint::Cycle(cycle) => state.cycle == cycle,
+                Breakpoint::Pc(pc) => fetching && state.pc == pc,
+            };
+            if hit {
+                events.push(Event::Breakpoint(breakpoint, state));
             }
-            Instruction::AddX(amount) => {
-                let last = states.last().unwrap();
-                states.push(State {
-                    cycle: last.cycle + 1,
-                    x: last.x + last.change,
-                    change: 0,
-                    instruction,
-                });
-                let last = states.last().unwrap();
-                states.push(State {
-                    cycle: last.cycle + 1,
-                    x: last.x,
-                    change: amount,
-                    instruction,
-                });
+        }
+        if self.watch_x && state.change != 0 {
+            events.push(Event::Watch(state));
+        }
+        if events.is_empty() {
+            events.push(Event::Step(state));
+        }
+        Ok(events)
+    }
+
+    // runs until a cycle triggers a breakpoint or watch, or the program halts
+    pub fn run(&mut self) -> Result<Vec<Event>, Error> {
+        for _ in 0..self.max_cycles {
+            let events = self.step()?;
+            if !matches!(events[..], [Event::Step(_)]) {
+                return Ok(events);
             }
         }
+        Err(Error::General(format!(
+            "no breakpoint, watch or halt within {} cycles, stopped in cycle {}",
+            self.max_cycles, self.cpu.cycle
+        )))
+    }
+}
+
+pub fn execute(input: &str) -> Result<States, Error> {
+    let mut cpu = Cpu::new(load_program(input)?);
+    let mut states = Vec::new();
+    while let Some(state) = cpu.step()? {
+        if state.cycle > MAX_CYCLES {
+            return Err(Error::General(format!("program did not halt within {} cycles", MAX_CYCLES)));
+        }
+        states.push(state);
     }
-    let last = states.last().unwrap();
-    if last.change != 0 {
-        states.push(State {
-            cycle: last.cycle + 1,
-            x: last.x + last.change,
-            change: 0,
-            instruction: Instruction::Noop(),
-        });
+    if let Some(last) = states.last() {
+        if last.change != 0 {
+            states.push(State {
+                cycle: last.cycle + 1,
+                pc: cpu.pc,
+                x: cpu.x,
+                change: 0,
+                instruction: Instruction::Noop(),
+            });
+        }
     }
-    states.remove(0);
     Ok(States { states })
 }
 
-pub fn render_states(states: &States) -> Result<(), Error> {
-    for (pixel_index, state) in states.states.iter().enumerate() {
-        let pixel_index = pixel_index as i64 % 40;
-        if pixel_index == 0 {
-            println!();
-        }
-        if state.x == pixel_index - 1 || state.x == pixel_index || state.x == pixel_index + 1 {
-            print!("X");
-        } else {
-            print!(".");
+pub const CRT_WIDTH: usize = 40;
+pub const CRT_HEIGHT: usize = 6;
+
+// letters are 4 pixels wide and 6 high, with one empty column between them
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_SPACING: usize = 5;
+const FONT: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Y', "#...#....#.#..#...#...#."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crt {
+    pub pixels: [[bool; CRT_WIDTH]; CRT_HEIGHT],
+}
+
+impl Crt {
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y][x]
+    }
+
+    fn glyph(&self, index: usize) -> String {
+        let mut glyph = String::with_capacity(GLYPH_WIDTH * CRT_HEIGHT);
+        for row in self.pixels.iter() {
+            for &lit in row[index * GLYPH_SPACING..index * GLYPH_SPACING + GLYPH_WIDTH].iter() {
+                glyph.push(if lit { '#' } else { '.' });
+            }
         }
+        glyph
+    }
+
+    pub fn decode(&self) -> Result<String, Error> {
+        (0..CRT_WIDTH / GLYPH_SPACING)
+            .map(|index| {
+                let glyph = self.glyph(index);
+                FONT.iter()
+                    .find(|(_, pattern)| *pattern == glyph)
+                    .map(|(letter, _)| *letter)
+                    .ok_or_else(|| Error::General(format!("unknown letter at position {}: {}", index, glyph)))
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Crt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let screen = render_grid(CRT_WIDTH, CRT_HEIGHT, |x, y| if self.pixel(x, y) { '#' } else { '.' });
+        f.write_str(&screen)
     }
-    Ok(())
+}
+
+// the sprite is 3 pixels wide and centered on x, one pixel is drawn per cycle
+pub fn render_states(states: &States) -> Crt {
+    let mut crt = Crt {
+        pixels: [[false; CRT_WIDTH]; CRT_HEIGHT],
+    };
+    for (pixel_index, state) in states.states.iter().take(CRT_WIDTH * CRT_HEIGHT).enumerate() {
+        let (x, y) = (pixel_index % CRT_WIDTH, pixel_index / CRT_WIDTH);
+        crt.pixels[y][x] = (state.x - x as i64).abs() <= 1;
+    }
+    crt
 }
 
 #[test]
@@ -289,7 +543,18 @@ noop"#;
     assert_eq!(states.cycle(180).signal_strength(), 2880);
     assert_eq!(states.cycle(220).signal_strength(), 3960);
 
-    //render_states(&states)?;
+    let crt = render_states(&states);
+    assert_eq!(
+        crt.to_string(),
+        r#"##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+"#
+    );
+    assert!(crt.decode().is_err());
 
     let states = execute(&std::fs::read_to_string("input/day10")?)?;
     assert_eq!(
@@ -302,7 +567,107 @@ noop"#;
         12560
     );
 
-    //render_states(&states)?;
+    assert_eq!(render_states(&states).decode()?, "PLPAFBCL");
+
+    Ok(())
+}
+
+#[test]
+fn test_cpu() -> Result<(), Error> {
+    let program = load_program(&std::fs::read_to_string("input/day10")?)?;
+    let mut debugger = Debugger::new(Cpu::new(program.clone()));
+    debugger.breakpoints = (20..=220).step_by(40).map(Breakpoint::Cycle).collect();
+    let mut total = 0;
+    while let [Event::Breakpoint(_, state)] = debugger.run()?[..] {
+        total += state.signal_strength();
+    }
+    assert_eq!(total, 12560);
+
+    let mut cpu = Cpu::new(program);
+    assert_eq!(
+        cpu.run_to_cycle(20)?.unwrap().signal_strength(),
+        execute(&std::fs::read_to_string("input/day10")?)?.cycle(20).signal_strength()
+    );
+
+    let program = load_program(
+        r#"
+addx 4
+mulx 3
+skipz
+jmp 2
+addx -15
+skipnz
+addx 100
+noop"#,
+    )?;
+    assert_eq!(program[1].cycles(), 4);
+    assert_eq!(program[3], Instruction::Jmp(2));
+    assert_eq!(program[5].mnemonic(), "skipnz");
+    assert!("jmp".parse::<Instruction>().is_err());
+    assert!("noop 1".parse::<Instruction>().is_err());
+    assert!("divx 2".parse::<Instruction>().is_err());
+
+    let mut debugger = Debugger::new(Cpu::new(program.clone()));
+    debugger.breakpoints.push(Breakpoint::Pc(7));
+    debugger.watch_x = true;
+    let mut changes = Vec::new();
+    'running: loop {
+        for event in debugger.run()? {
+            match event {
+                Event::Watch(state) => changes.push((state.cycle, state.x + state.change)),
+                Event::Breakpoint(breakpoint, state) => {
+                    assert_eq!(breakpoint, Breakpoint::Pc(7));
+                    assert_eq!(state.cycle, 11);
+                }
+                Event::Halted | Event::Step(_) => break 'running,
+            }
+        }
+    }
+    // skipz does not skip, jmp 2 jumps over addx -15 to skipnz, which skips addx 100
+    assert_eq!(changes, vec![(2, 5), (6, 15)]);
+    assert_eq!(debugger.cpu.cycle, 11);
+    assert_eq!(debugger.cpu.x, 15);
+
+    // a breakpoint and a watch in the same cycle are both reported
+    let mut debugger = Debugger::new(Cpu::new(program.clone()));
+    debugger.breakpoints = vec![Breakpoint::Cycle(2), Breakpoint::Pc(1)];
+    debugger.watch_x = true;
+    let events = debugger.run()?;
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], Event::Breakpoint(Breakpoint::Cycle(2), _)));
+    assert!(matches!(events[1], Event::Watch(State { cycle: 2, .. })));
+    assert!(matches!(
+        debugger.run()?[..],
+        [Event::Breakpoint(Breakpoint::Pc(1), State { cycle: 3, .. })]
+    ));
+
+    for (opcode, (mnemonic, cycles, build)) in INSTRUCTION_SET.iter().enumerate() {
+        let (text, instruction) = match build(None) {
+            Some(instruction) => (mnemonic.to_string(), instruction),
+            None => (format!("{} 1", mnemonic), build(Some(1)).unwrap()),
+        };
+        assert_eq!(text.parse::<Instruction>()?, instruction);
+        assert_eq!(instruction.opcode(), opcode);
+        assert_eq!((instruction.mnemonic(), instruction.cycles()), (*mnemonic, *cycles));
+    }
+
+    let mut cpu = Cpu::new(vec![Instruction::Jmp(-1)]);
+    assert!(cpu.step().is_ok());
+    assert!(cpu.step().is_err());
+    let mut cpu = Cpu::new(vec![Instruction::AddX(i64::MAX)]);
+    assert!(cpu.run_to_cycle(2).is_err());
+
+    // a program that never halts
+    assert!(execute("noop\njmp -1").is_err());
+    let mut debugger = Debugger::new(Cpu::new(load_program("jmp 0")?));
+    debugger.max_cycles = 100;
+    assert!(debugger.run().is_err());
+    assert_eq!(debugger.cpu.cycle, 100);
+    debugger.breakpoints.push(Breakpoint::Cycle(150));
+    assert!(matches!(
+        debugger.run()?[..],
+        [Event::Breakpoint(Breakpoint::Cycle(150), State { cycle: 150, .. })]
+    ));
 
     Ok(())
 }
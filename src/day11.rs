@@ -2,6 +2,7 @@
 // part1: What is the level of monkey business after 20 rounds of stuff-slinging simian shenanigans?
 // part2: (mega big numbers) what is the level of monkey business after 10000 rounds?
 use crate::error::Error;
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -10,65 +11,320 @@ pub struct Game {
     pub modulus: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Operation {
-    Times(usize),
-    Square,
-    Plus(usize),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
 }
 
-impl Operation {
-    fn apply(&self, value: usize) -> usize {
+impl Operator {
+    fn from_token(token: &str) -> Option<Operator> {
+        match token {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Subtract),
+            "*" => Some(Operator::Multiply),
+            "/" => Some(Operator::Divide),
+            _ => None,
+        }
+    }
+
+    fn binds_tighter(&self) -> bool {
+        matches!(self, Operator::Multiply | Operator::Divide)
+    }
+}
+
+// right hand side of `new = ...`, evaluated with the usual precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Old,
+    Constant(usize),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+impl Expression {
+    pub fn evaluate(&self, old: usize) -> Result<usize, Error> {
         match self {
-            Operation::Times(amount) => value * *amount,
-            Operation::Square => value * value,
-            Operation::Plus(amount) => value + *amount,
+            Expression::Old => Ok(old),
+            Expression::Constant(value) => Ok(*value),
+            Expression::Binary(lhs, operator, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(old)?, rhs.evaluate(old)?);
+                let result = match operator {
+                    Operator::Add => lhs.checked_add(rhs),
+                    Operator::Subtract => lhs.checked_sub(rhs),
+                    Operator::Multiply => lhs.checked_mul(rhs),
+                    Operator::Divide => lhs.checked_div(rhs),
+                };
+                result.ok_or_else(|| Error::General(format!("cannot evaluate {} {:?} {}", lhs, operator, rhs)))
+            }
+        }
+    }
+
+    // only adds and multiplies, so reducing `old` modulo m leaves the result the same modulo m
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Expression::Old | Expression::Constant(_) => true,
+            Expression::Binary(lhs, operator, rhs) => {
+                matches!(operator, Operator::Add | Operator::Multiply) && lhs.is_modular() && rhs.is_modular()
+            }
+        }
+    }
+
+    fn parse_operand(token: Option<&str>) -> Result<Expression, Error> {
+        match token {
+            Some("old") => Ok(Expression::Old),
+            Some(token) => Ok(Expression::Constant(token.parse()?)),
+            None => Err(Error::Parse("missing operand".to_string())),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        // `sum` is everything left of the last + or -, `product` the term being built after it
+        let mut sum: Option<(Expression, Operator)> = None;
+        let mut product = Expression::parse_operand(tokens.next())?;
+        while let Some(token) = tokens.next() {
+            let operator = Operator::from_token(token).ok_or_else(|| Error::Parse(format!("invalid operator: {}", token)))?;
+            let operand = Expression::parse_operand(tokens.next())?;
+            if operator.binds_tighter() {
+                product = Expression::Binary(Box::new(product), operator, Box::new(operand));
+            } else {
+                let lhs = match sum.take() {
+                    Some((lhs, pending)) => Expression::Binary(Box::new(lhs), pending, Box::new(product)),
+                    None => product,
+                };
+                sum = Some((lhs, operator));
+                product = operand;
+            }
         }
+        Ok(match sum {
+            Some((lhs, pending)) => Expression::Binary(Box::new(lhs), pending, Box::new(product)),
+            None => product,
+        })
     }
 }
 
 #[derive(Debug)]
 pub struct Monkey {
     pub items: Vec<usize>,
-    pub operation: Operation,
+    pub operation: Expression,
     pub divisible_by: usize,
     pub if_true: usize,
     pub if_false: usize,
     pub num_inspections: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorryPolicy {
+    // part1: worry drops after every inspection
+    DivideBy(usize),
+    // part2: keep worry levels small. reducing modulo a multiple of every test divisor keeps the outcome
+    // of the tests, but only while operations add and multiply, so it is rejected for anything else
+    Modulo(usize),
+    Unchanged,
+}
+
+impl WorryPolicy {
+    fn apply(&self, worry: usize) -> Result<usize, Error> {
+        match self {
+            WorryPolicy::DivideBy(divisor) => worry
+                .checked_div(*divisor)
+                .ok_or_else(|| Error::General("division by zero".to_string())),
+            WorryPolicy::Modulo(modulus) => worry.checked_rem(*modulus).ok_or_else(|| Error::General("modulo zero".to_string())),
+            WorryPolicy::Unchanged => Ok(worry),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    // items held by every monkey at the end of the round
+    pub items: Vec<Vec<usize>>,
+    // throws[from][to]
+    pub throws: Vec<Vec<usize>>,
+}
+
+// rounds are counted from the start of the simulation, and an item is back in the state it had at
+// round `start` every `length` rounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemCycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+#[derive(Debug)]
+struct ItemHistory {
+    cycle: ItemCycle,
+    // monkeys that inspected the item in every round before the cycle repeats
+    inspected_by: Vec<Vec<usize>>,
+}
+
+impl ItemHistory {
+    fn inspections(&self, rounds: std::ops::Range<usize>, inspections: &mut [usize], times: usize) {
+        for round in rounds {
+            for &monkey in self.inspected_by[round].iter() {
+                inspections[monkey] += times;
+            }
+        }
+    }
+
+    fn inspections_after(&self, num_rounds: usize, inspections: &mut [usize]) {
+        let ItemCycle { start, length } = self.cycle;
+        if num_rounds <= start {
+            self.inspections(0..num_rounds, inspections, 1);
+            return;
+        }
+        self.inspections(0..start, inspections, 1);
+        self.inspections(start..start + length, inspections, (num_rounds - start) / length);
+        self.inspections(start..start + (num_rounds - start) % length, inspections, 1);
+    }
+}
+
+fn monkey_business_of(inspections: &[usize]) -> Option<usize> {
+    let mut inspections = inspections.to_vec();
+    inspections.sort_by(|a, b| b.cmp(a));
+    inspections[0].checked_mul(inspections[1])
+}
+
 impl Game {
-    pub fn monkey_business(&self) -> usize {
-        let mut inspections: Vec<usize> = self.monkeys.iter().map(|m| m.num_inspections).collect();
-        inspections.sort_by(|a, b| b.cmp(a));
-        inspections[0] * inspections[1]
+    // None if it does not fit in usize
+    pub fn monkey_business(&self) -> Option<usize> {
+        monkey_business_of(&self.monkeys.iter().map(|m| m.num_inspections).collect::<Vec<usize>>())
+    }
+
+    fn check_policy(&self, policy: WorryPolicy) -> Result<(), Error> {
+        if let WorryPolicy::Modulo(_) = policy {
+            if let Some(index) = self.monkeys.iter().position(|m| !m.operation.is_modular()) {
+                return Err(Error::General(format!(
+                    "monkey {} subtracts or divides, so worry levels cannot be reduced modulo",
+                    index
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // returns the new worry level and the monkey it is thrown to
+    fn inspect(&self, monkey_index: usize, item: usize, policy: WorryPolicy) -> Result<(usize, usize), Error> {
+        let monkey = &self.monkeys[monkey_index];
+        let item = policy.apply(monkey.operation.evaluate(item)?)?;
+        let target_monkey = if item % monkey.divisible_by == 0 {
+            monkey.if_true
+        } else {
+            monkey.if_false
+        };
+        Ok((item, target_monkey))
     }
 
-    pub fn simulate_rounds(&mut self, num_rounds: usize, part2: bool) {
+    pub fn simulate_round(&mut self, policy: WorryPolicy) -> Result<Round, Error> {
+        self.check_policy(policy)?;
+        let mut throws = Vec::with_capacity(self.monkeys.len());
         let mut changes = Vec::new();
+        for monkey_index in 0..self.monkeys.len() {
+            let mut thrown = vec![0; self.monkeys.len()];
+            // inspect every item before touching the monkey, so an error leaves it holding all of them
+            for &item in self.monkeys[monkey_index].items.iter() {
+                let (item, target_monkey) = self.inspect(monkey_index, item, policy)?;
+                thrown[target_monkey] += 1;
+                changes.push((target_monkey, item));
+            }
+            self.monkeys[monkey_index].num_inspections += self.monkeys[monkey_index].items.len();
+            self.monkeys[monkey_index].items.clear();
+            for (target_monkey, item) in changes.drain(..) {
+                self.monkeys[target_monkey].items.push(item);
+            }
+            throws.push(thrown);
+        }
+        Ok(Round {
+            items: self.monkeys.iter().map(|m| m.items.clone()).collect(),
+            throws,
+        })
+    }
+
+    pub fn simulate_rounds(&mut self, num_rounds: usize, policy: WorryPolicy) -> Result<(), Error> {
+        self.check_policy(policy)?;
         for _round in 0..num_rounds {
-            for monkey_index in 0..self.monkeys.len() {
-                self.monkeys[monkey_index].num_inspections += self.monkeys[monkey_index].items.len();
-                let items: Vec<usize> = self.monkeys[monkey_index].items.drain(..).collect();
-                for mut item in items {
-                    item = self.monkeys[monkey_index].operation.apply(item);
-                    if !part2 {
-                        item /= 3;
-                    } else {
-                        item %= self.modulus;
-                    }
-                    let target_monkey = if item % self.monkeys[monkey_index].divisible_by == 0 {
-                        self.monkeys[monkey_index].if_true
-                    } else {
-                        self.monkeys[monkey_index].if_false
-                    };
-                    changes.push((target_monkey, item));
-                }
-                for (target_monkey, item) in changes.drain(..) {
-                    self.monkeys[target_monkey].items.push(item);
+            self.simulate_round(policy)?;
+        }
+        Ok(())
+    }
+
+    pub fn history(&mut self, num_rounds: usize, policy: WorryPolicy) -> Result<Vec<Round>, Error> {
+        (0..num_rounds).map(|_| self.simulate_round(policy)).collect()
+    }
+
+    // every item moves independently: a round takes it from (monkey, worry) to a new (monkey, worry),
+    // so each item repeats once it is back in a state it has been in at the start of a round. that is only
+    // bound to happen when worry levels are kept below a modulus, any other policy is rejected
+    fn item_history(&self, monkey_index: usize, item: usize, policy: WorryPolicy) -> Result<ItemHistory, Error> {
+        if !matches!(policy, WorryPolicy::Modulo(_)) {
+            return Err(Error::General(format!(
+                "item cycles need worry levels reduced modulo, not {:?}",
+                policy
+            )));
+        }
+        self.check_policy(policy)?;
+        let mut seen = HashMap::new();
+        let mut inspected_by = Vec::new();
+        let mut state = (monkey_index, item);
+        while !seen.contains_key(&state) {
+            seen.insert(state, inspected_by.len());
+            let (mut monkey_index, mut item) = state;
+            let mut inspected = Vec::new();
+            loop {
+                inspected.push(monkey_index);
+                let (new_item, target_monkey) = self.inspect(monkey_index, item, policy)?;
+                item = new_item;
+                // monkeys later in the round get to throw it again in the same round
+                if target_monkey <= monkey_index {
+                    state = (target_monkey, item);
+                    break;
                 }
+                monkey_index = target_monkey;
+            }
+            inspected_by.push(inspected);
+        }
+        let start = seen[&state];
+        Ok(ItemHistory {
+            cycle: ItemCycle {
+                start,
+                length: inspected_by.len() - start,
+            },
+            inspected_by,
+        })
+    }
+
+    // cycle of every item currently held, in monkey order
+    pub fn item_cycles(&self, policy: WorryPolicy) -> Result<Vec<ItemCycle>, Error> {
+        let mut cycles = Vec::new();
+        for (monkey_index, monkey) in self.monkeys.iter().enumerate() {
+            for &item in monkey.items.iter() {
+                cycles.push(self.item_history(monkey_index, item, policy)?.cycle);
+            }
+        }
+        Ok(cycles)
+    }
+
+    // inspections per monkey after `num_rounds` more rounds, extrapolated from the item cycles.
+    // only works with `WorryPolicy::Modulo`, as in item_cycles
+    pub fn inspections_after(&self, num_rounds: usize, policy: WorryPolicy) -> Result<Vec<usize>, Error> {
+        let mut inspections: Vec<usize> = self.monkeys.iter().map(|m| m.num_inspections).collect();
+        for (monkey_index, monkey) in self.monkeys.iter().enumerate() {
+            for &item in monkey.items.iter() {
+                self.item_history(monkey_index, item, policy)?
+                    .inspections_after(num_rounds, &mut inspections);
             }
         }
+        Ok(inspections)
+    }
+
+    pub fn monkey_business_after(&self, num_rounds: usize, policy: WorryPolicy) -> Result<usize, Error> {
+        monkey_business_of(&self.inspections_after(num_rounds, policy)?)
+            .ok_or_else(|| Error::General(format!("monkey business after {} rounds does not fit in usize", num_rounds)))
     }
 }
 
@@ -77,44 +333,35 @@ impl FromStr for Game {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut monkeys = Vec::new();
         let mut lines = s.trim_start().trim_end().lines();
-        loop {
-            let (_line0, line1, line2, line3, line4, line5) =
-                (lines.next(), lines.next(), lines.next(), lines.next(), lines.next(), lines.next());
-            if line5.is_none() {
-                break;
-            }
+        while let Some(line0) = lines.next() {
+            let mut next_line = |what: &str| {
+                lines
+                    .next()
+                    .ok_or_else(|| Error::Parse(format!("{} missing for {}", what, line0.trim())))
+            };
+            let (line1, line2, line3, line4, line5) = (
+                next_line("starting items")?,
+                next_line("operation")?,
+                next_line("test")?,
+                next_line("if true")?,
+                next_line("if false")?,
+            );
 
             let items = line1
-                .unwrap()
                 .trim_start()
-                .trim_start_matches("Starting items: ")
+                .trim_start_matches("Starting items:")
                 .split(',')
-                .map(|item| item.trim().parse::<usize>().unwrap())
-                .collect();
-
-            let line2 = line2.unwrap().trim_start().trim_start_matches("Operation: new = old ").to_string();
-            let operation = if line2.starts_with("* old") {
-                Operation::Square
-            } else if line2.starts_with('*') {
-                Operation::Times(line2.trim_start_matches("* ").parse()?)
-            } else if line2.starts_with('+') {
-                Operation::Plus(line2.trim_start_matches("+ ").parse()?)
-            } else {
-                panic!("invalid operation");
-            };
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()?;
 
-            let divide_by = line3.unwrap().trim_start().trim_start_matches("Test: divisible by ").parse()?;
+            let operation = line2.trim_start().trim_start_matches("Operation: new = ").parse()?;
 
-            let if_true = line4
-                .unwrap()
-                .trim_start()
-                .trim_start_matches("If true: throw to monkey ")
-                .parse()?;
-            let if_false = line5
-                .unwrap()
-                .trim_start()
-                .trim_start_matches("If false: throw to monkey ")
-                .parse()?;
+            let divide_by = line3.trim_start().trim_start_matches("Test: divisible by ").parse()?;
+
+            let if_true = line4.trim_start().trim_start_matches("If true: throw to monkey ").parse()?;
+            let if_false = line5.trim_start().trim_start_matches("If false: throw to monkey ").parse()?;
 
             monkeys.push(Monkey {
                 items,
@@ -135,9 +382,8 @@ impl FromStr for Game {
     }
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 Monkey 0:
   Starting items: 79, 98
   Operation: new = old * 19
@@ -166,29 +412,152 @@ Monkey 3:
     If true: throw to monkey 0
     If false: throw to monkey 1"#;
 
-    let mut game = input.parse::<Game>()?;
-    game.simulate_rounds(20, false);
-    assert_eq!(game.monkey_business(), 10605);
+#[test]
+fn test() -> Result<(), Error> {
+    let mut game = EXAMPLE.parse::<Game>()?;
+    game.simulate_rounds(20, WorryPolicy::DivideBy(3))?;
+    assert_eq!(game.monkey_business(), Some(10605));
+
+    let mut game = EXAMPLE.parse::<Game>()?;
+    game.simulate_rounds(20, WorryPolicy::Modulo(game.modulus))?;
+    assert_eq!(game.monkey_business(), Some(99 * 103));
+
+    let mut game = EXAMPLE.parse::<Game>()?;
+    game.simulate_rounds(1000, WorryPolicy::Modulo(game.modulus))?;
+    assert_eq!(game.monkey_business(), Some(5204 * 5192));
+
+    let mut game = EXAMPLE.parse::<Game>()?;
+    game.simulate_rounds(10000, WorryPolicy::Modulo(game.modulus))?;
+    assert_eq!(game.monkey_business(), Some(52166 * 52013));
+
+    let mut game = std::fs::read_to_string("input/day11")?.parse::<Game>()?;
+    game.simulate_rounds(20, WorryPolicy::DivideBy(3))?;
+    assert_eq!(game.monkey_business(), Some(55458));
+
+    let mut game = std::fs::read_to_string("input/day11")?.parse::<Game>()?;
+    game.simulate_rounds(10_000, WorryPolicy::Modulo(game.modulus))?;
+    assert_eq!(game.monkey_business(), Some(14508081294));
+
+    Ok(())
+}
+
+#[test]
+fn test_expression() -> Result<(), Error> {
+    assert_eq!("old * 19".parse::<Expression>()?.evaluate(2)?, 38);
+    assert_eq!("old * old".parse::<Expression>()?.evaluate(7)?, 49);
+    assert_eq!("old + old".parse::<Expression>()?.evaluate(7)?, 14);
+    assert_eq!("old - 3".parse::<Expression>()?.evaluate(7)?, 4);
+    assert_eq!("100 / old".parse::<Expression>()?.evaluate(7)?, 14);
+    assert_eq!("3 + old * 2 - 1".parse::<Expression>()?.evaluate(5)?, 12);
+    assert_eq!("20 - old - 3".parse::<Expression>()?.evaluate(5)?, 12);
+    assert_eq!(
+        "old - 3".parse::<Expression>()?,
+        Expression::Binary(Box::new(Expression::Old), Operator::Subtract, Box::new(Expression::Constant(3)))
+    );
+
+    assert!("old - 3".parse::<Expression>()?.evaluate(2).is_err());
+    assert!("old / 0".parse::<Expression>()?.evaluate(2).is_err());
+    assert!("old * old".parse::<Expression>()?.evaluate(usize::MAX / 2).is_err());
+    assert!("old % 3".parse::<Expression>().is_err());
+    assert!("old *".parse::<Expression>().is_err());
+    assert!("".parse::<Expression>().is_err());
+
+    let input = r#"
+Monkey 0:
+  Starting items: 10, 20
+  Operation: new = old - 1
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items: 5
+  Operation: new = old + old
+  Test: divisible by 5
+    If true: throw to monkey 0
+    If false: throw to monkey 0"#;
 
     let mut game = input.parse::<Game>()?;
-    game.simulate_rounds(20, true);
-    assert_eq!(game.monkey_business(), 99 * 103);
+    game.simulate_rounds(1, WorryPolicy::DivideBy(3))?;
+    assert_eq!(game.monkeys[0].items, vec![3, 2, 4]);
+    assert!(game.monkeys[1].items.is_empty());
 
+    // reduced worry levels would make `old - 1` underflow or change the test outcome
     let mut game = input.parse::<Game>()?;
-    game.simulate_rounds(1000, true);
-    assert_eq!(game.monkey_business(), 5204 * 5192);
+    assert!(!game.monkeys[0].operation.is_modular());
+    assert!(game.monkeys[1].operation.is_modular());
+    assert!(game.simulate_rounds(1, WorryPolicy::Modulo(10)).is_err());
+    assert!(game.simulate_round(WorryPolicy::Modulo(10)).is_err());
+    assert!(game.monkey_business_after(10, WorryPolicy::Modulo(10)).is_err());
+    assert!(game.item_cycles(WorryPolicy::Modulo(10)).is_err());
+    assert_eq!(game.monkeys[0].num_inspections, 0);
+
+    // a failed inspection leaves the monkey's items and count alone
+    let input = r#"
+Monkey 0:
+  Starting items: 10, 3
+  Operation: new = old - 5
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1
 
+Monkey 1:
+  Starting items:
+  Operation: new = old + 1
+  Test: divisible by 5
+    If true: throw to monkey 0
+    If false: throw to monkey 0"#;
     let mut game = input.parse::<Game>()?;
-    game.simulate_rounds(10000, true);
-    assert_eq!(game.monkey_business(), 52166 * 52013);
+    assert!(game.monkeys[1].items.is_empty());
+    assert!(game.simulate_round(WorryPolicy::DivideBy(1)).is_err());
+    assert_eq!(game.monkeys[0].items, vec![10, 3]);
+    assert_eq!(game.monkeys[0].num_inspections, 0);
+    assert!(game.monkeys[1].items.is_empty());
 
-    let mut game = std::fs::read_to_string("input/day11")?.parse::<Game>()?;
-    game.simulate_rounds(20, false);
-    assert_eq!(game.monkey_business(), 55458);
+    assert!(input.replace("10, 3", "10, x").parse::<Game>().is_err());
+    assert!(input.replace("10, 3", "10, -3").parse::<Game>().is_err());
+    assert!(input.lines().take(10).collect::<Vec<_>>().join("\n").parse::<Game>().is_err());
 
-    let mut game = std::fs::read_to_string("input/day11")?.parse::<Game>()?;
-    game.simulate_rounds(10_000, true);
-    assert_eq!(game.monkey_business(), 14508081294);
+    Ok(())
+}
+
+#[test]
+fn test_history() -> Result<(), Error> {
+    let mut game = EXAMPLE.parse::<Game>()?;
+    let history = game.history(2, WorryPolicy::DivideBy(3))?;
+    assert_eq!(
+        history[0].items,
+        vec![vec![20, 23, 27, 26], vec![2080, 25, 167, 207, 401, 1046], vec![], vec![]]
+    );
+    assert_eq!(history[0].throws[0], vec![0, 0, 0, 2]);
+    assert_eq!(history[0].throws[2], vec![0, 1, 0, 2]);
+    assert_eq!(history[1].items[0], vec![695, 10, 71, 135, 350]);
+    let inspections: usize = game.monkeys.iter().map(|m| m.num_inspections).sum();
+    assert_eq!(
+        history.iter().flat_map(|round| round.throws.iter().flatten()).sum::<usize>(),
+        inspections
+    );
+
+    let game = EXAMPLE.parse::<Game>()?;
+    let policy = WorryPolicy::Modulo(game.modulus);
+    assert_eq!(game.monkey_business_after(20, policy)?, 99 * 103);
+    assert_eq!(game.monkey_business_after(10_000, policy)?, 52166 * 52013);
+    let mut simulated = EXAMPLE.parse::<Game>()?;
+    simulated.simulate_rounds(1234, policy)?;
+    assert_eq!(
+        game.inspections_after(1234, policy)?,
+        simulated.monkeys.iter().map(|m| m.num_inspections).collect::<Vec<_>>()
+    );
+    assert_eq!(game.item_cycles(policy)?[0], ItemCycle { start: 138, length: 171 });
+    assert_eq!(game.inspections_after(1_000_000_000, policy)?[0], 5217653496);
+    assert!(game.monkey_business_after(1_000_000_000, policy).is_err());
+    // worry levels that keep growing would never repeat
+    assert!(game.item_cycles(WorryPolicy::Unchanged).is_err());
+    assert!(game.inspections_after(20, WorryPolicy::DivideBy(3)).is_err());
+    assert!(game.monkey_business_after(20, WorryPolicy::Unchanged).is_err());
+
+    let game = std::fs::read_to_string("input/day11")?.parse::<Game>()?;
+    assert_eq!(game.monkey_business_after(10_000, WorryPolicy::Modulo(game.modulus))?, 14508081294);
 
     Ok(())
 }
@@ -3,24 +3,119 @@
 // part2: What is the fewest steps required to move starting from any square with elevation a to the location that should get the best signal?
 
 use crate::error::Error;
+use crate::utils::render_grid;
 use petgraph::algo::astar;
 use petgraph::graph::{DefaultIx, DiGraph, NodeIndex};
 use petgraph::Graph;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use std::str::FromStr;
 
 type Pos = (usize, usize);
 
+// cost from every square to the cheapest target, None where no target can be reached
+#[derive(Debug)]
+pub struct DistanceMap {
+    width: usize,
+    // cost and, to break ties between routes of the same cost, number of steps
+    distances: Vec<Option<(usize, usize)>>,
+    // the square to go to next on a shortest path
+    next: Vec<Option<Pos>>,
+}
+
+impl DistanceMap {
+    // the number of steps with CostModel::Steps
+    pub fn distance(&self, pos: Pos) -> Option<usize> {
+        self.cost_and_steps(pos).map(|(cost, _)| cost)
+    }
+
+    fn cost_and_steps(&self, pos: Pos) -> Option<(usize, usize)> {
+        self.distances[pos.1 * self.width + pos.0]
+    }
+
+    // a shortest path from `start` to a target, including both
+    pub fn path_from(&self, start: Pos) -> Option<Vec<Pos>> {
+        self.distance(start)?;
+        let mut path = vec![start];
+        let mut current = start;
+        while let Some(next) = self.next[current.1 * self.width + current.0] {
+            path.push(next);
+            current = next;
+        }
+        Some(path)
+    }
+}
+
+// which steps between neighbouring squares are allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClimbingRules {
+    pub max_up: usize,
+    // None means any drop is allowed
+    pub max_down: Option<usize>,
+    pub diagonal: bool,
+}
+
+impl Default for ClimbingRules {
+    fn default() -> Self {
+        Self {
+            max_up: 1,
+            max_down: None,
+            diagonal: false,
+        }
+    }
+}
+
+// the weight of a single step in the graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostModel {
+    Steps,
+    Energy {
+        per_step: usize,
+        per_height_up: usize,
+        per_height_down: usize,
+    },
+}
+
+impl CostModel {
+    pub fn cost(&self, height_from: usize, height_to: usize) -> usize {
+        match *self {
+            CostModel::Steps => 1,
+            CostModel::Energy {
+                per_step,
+                per_height_up,
+                per_height_down,
+            } => per_step + per_height_up * height_to.saturating_sub(height_from) + per_height_down * height_from.saturating_sub(height_to),
+        }
+    }
+
+    // a lower bound for the cost of any single step
+    fn min_step_cost(&self) -> usize {
+        match *self {
+            CostModel::Steps => 1,
+            CostModel::Energy { per_step, .. } => per_step,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Game {
     pub map: Vec<Vec<char>>,
     pub start_position: Pos,
     pub end_position: Pos,
+    rules: ClimbingRules,
+    costs: CostModel,
 }
 
 impl FromStr for Game {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut map = Vec::new();
+        Game::new(s, ClimbingRules::default(), CostModel::Steps)
+    }
+}
+
+impl Game {
+    pub fn new(s: &str, rules: ClimbingRules, costs: CostModel) -> Result<Self, Error> {
+        let mut map: Vec<Vec<char>> = Vec::new();
         let mut start_position: Option<Pos> = None;
         let mut end_position: Option<Pos> = None;
         for (row_index, line) in s.trim_start().trim_end().lines().enumerate() {
@@ -40,17 +135,20 @@ impl FromStr for Game {
                 };
                 row.push(height);
             }
+            if map.first().is_some_and(|first| first.len() != row.len()) {
+                return Err(Error::Parse(format!("row {} has a different width", row_index)));
+            }
             map.push(row);
         }
         Ok(Game {
             map,
-            start_position: start_position.unwrap(),
-            end_position: end_position.unwrap(),
+            start_position: start_position.ok_or_else(|| Error::Parse("missing S".to_string()))?,
+            end_position: end_position.ok_or_else(|| Error::Parse("missing E".to_string()))?,
+            rules,
+            costs,
         })
     }
-}
 
-impl Game {
     pub fn map_width(&self) -> usize {
         self.map.first().unwrap().len() as usize
     }
@@ -86,6 +184,21 @@ impl Game {
             edges.push(((x, y), (x - 1, y)));
         }
 
+        if self.rules.diagonal {
+            if y > 0 && x > 0 {
+                edges.push(((x, y), (x - 1, y - 1)));
+            }
+            if y > 0 && x < self.map_width() - 1 {
+                edges.push(((x, y), (x + 1, y - 1)));
+            }
+            if y < self.map_height() - 1 && x < self.map_width() - 1 {
+                edges.push(((x, y), (x + 1, y + 1)));
+            }
+            if y < self.map_height() - 1 && x > 0 {
+                edges.push(((x, y), (x - 1, y + 1)));
+            }
+        }
+
         edges
     }
 
@@ -97,30 +210,93 @@ impl Game {
         (node.index() % self.map_width(), node.index() / self.map_width())
     }
 
-    pub fn minimum_steps_from_any_a(&self) -> usize {
-        let mut min_steps: Option<usize> = None;
-        let graph = self.get_graph();
+    pub fn minimum_steps_from_any_a(&self) -> Option<usize> {
+        self.best_start(0).map(|(_, steps)| steps)
+    }
+
+    pub fn can_step(&self, from: Pos, to: Pos) -> bool {
+        let (height_from, height_to) = (self.height_at(from.0, from.1), self.height_at(to.0, to.1));
+        if height_to >= height_from {
+            height_to - height_from <= self.rules.max_up
+        } else {
+            match self.rules.max_down {
+                Some(max_down) => height_from - height_to <= max_down,
+                None => true,
+            }
+        }
+    }
+
+    pub fn step_cost(&self, from: Pos, to: Pos) -> usize {
+        self.costs.cost(self.height_at(from.0, from.1), self.height_at(to.0, to.1))
+    }
+
+    // admissible estimate of the cost from `pos` to the end
+    fn remaining_cost_estimate(&self, pos: Pos) -> usize {
+        let (dx, dy) = (pos.0.abs_diff(self.end_position.0), pos.1.abs_diff(self.end_position.1));
+        let steps = if self.rules.diagonal { dx.max(dy) } else { dx + dy };
+        steps * self.costs.min_step_cost()
+    }
+
+    // dijkstra backwards from all targets at once. among routes of the same cost the one with the fewest steps wins,
+    // so shortest paths stay finite even when some steps cost nothing
+    pub fn distances_to(&self, targets: &[Pos]) -> DistanceMap {
+        let mut distances = DistanceMap {
+            width: self.map_width(),
+            distances: vec![None; self.map_width() * self.map_height()],
+            next: vec![None; self.map_width() * self.map_height()],
+        };
+        let mut queue = BinaryHeap::new();
+        for &target in targets {
+            if distances.distance(target).is_none() {
+                distances.distances[target.1 * distances.width + target.0] = Some((0, 0));
+                queue.push(Reverse(((0, 0), target)));
+            }
+        }
+        while let Some(Reverse(((cost, steps), to))) = queue.pop() {
+            if distances.cost_and_steps(to) != Some((cost, steps)) {
+                continue;
+            }
+            for (_, from) in self.get_edges_for_pos(to.0, to.1) {
+                if !self.can_step(from, to) {
+                    continue;
+                }
+                let index = from.1 * distances.width + from.0;
+                let via_to = (cost + self.step_cost(from, to), steps + 1);
+                if !matches!(distances.distances[index], Some(known) if known <= via_to) {
+                    distances.distances[index] = Some(via_to);
+                    distances.next[index] = Some(to);
+                    queue.push(Reverse((via_to, from)));
+                }
+            }
+        }
+        distances
+    }
+
+    pub fn distances_to_end(&self) -> DistanceMap {
+        self.distances_to(&[self.end_position])
+    }
+
+    // the square of the given height cheapest to reach the end from, and its cost
+    pub fn best_start(&self, height: usize) -> Option<(Pos, usize)> {
+        let distances = self.distances_to_end();
+        let mut best: Option<(Pos, usize)> = None;
         for y in 0..self.map_height() {
             for x in 0..self.map_width() {
-                if self.height_at(x, y) == 0 {
-                    let steps = self.minimum_steps_from(&graph, (x, y));
-                    if let Some(steps) = steps {
-                        if let Some(value) = min_steps {
-                            if steps < value {
-                                min_steps = Some(steps);
-                            }
-                        } else {
-                            min_steps = Some(steps);
-                        }
+                if self.height_at(x, y) != height {
+                    continue;
+                }
+                if let Some(steps) = distances.distance((x, y)) {
+                    if !matches!(best, Some((_, best_steps)) if best_steps <= steps) {
+                        best = Some(((x, y), steps));
                     }
                 }
             }
         }
-        min_steps.unwrap()
+        best
     }
 
-    pub fn get_graph(&self) -> Graph<usize, Pos> {
-        let mut graph: Graph<usize, Pos> = DiGraph::new();
+    pub fn get_graph(&self) -> Graph<usize, usize> {
+        let mut graph: Graph<usize, usize> = DiGraph::new();
 
         // one node will be created for each point on the map
         for _ in 0..self.map_width() * self.map_height() {
@@ -136,43 +312,37 @@ impl Game {
             }
         }
 
-        // remove edges that are not possible and map them from (pos, pos) to (node, node)
-        let edges: Vec<(NodeIndex<DefaultIx>, NodeIndex<DefaultIx>)> = edges
+        // remove edges that are not possible and map them from (pos, pos) to (node, node, cost)
+        let edges: Vec<(NodeIndex<DefaultIx>, NodeIndex<DefaultIx>, usize)> = edges
             .into_iter()
-            .filter(|(from, to)| {
-                let height_from = self.height_at(from.0, from.1) as i64;
-                let height_to = self.height_at(to.0, to.1) as i64;
-                height_to - height_from <= 1
-            })
-            .map(|(from, to)| (self.get_node_from_pos(from), self.get_node_from_pos(to)))
+            .filter(|(from, to)| self.can_step(*from, *to))
+            .map(|(from, to)| (self.get_node_from_pos(from), self.get_node_from_pos(to), self.step_cost(from, to)))
             .collect();
 
         graph.extend_with_edges(&edges);
 
         graph
     }
-    pub fn minimum_steps_from(&self, graph: &Graph<usize, Pos>, start_position: Pos) -> Option<usize> {
+
+    // total cost of the cheapest route to the end, which is the number of steps with CostModel::Steps
+    pub fn minimum_steps_from(&self, graph: &Graph<usize, usize>, start_position: Pos) -> Option<usize> {
         self.minimum_steps_from_dijkstra(graph, start_position)
     }
 
-    pub fn minimum_steps_from_dijkstra(&self, graph: &Graph<usize, Pos>, start_position: Pos) -> Option<usize> {
+    pub fn minimum_steps_from_dijkstra(&self, graph: &Graph<usize, usize>, start_position: Pos) -> Option<usize> {
         let finish_node = self.get_node_from_pos(self.end_position);
-        let path = petgraph::algo::dijkstra(&graph, self.get_node_from_pos(start_position), Some(finish_node), |_| 1usize);
+        let path = petgraph::algo::dijkstra(&graph, self.get_node_from_pos(start_position), Some(finish_node), |e| *e.weight());
         path.get(&finish_node).copied()
     }
 
-    pub fn _minimum_steps_from_astar(&self, graph: &Graph<usize, Pos>, start_position: Pos) -> Option<usize> {
+    pub fn _minimum_steps_from_astar(&self, graph: &Graph<usize, usize>, start_position: Pos) -> Option<usize> {
         let finish_node = self.get_node_from_pos(self.end_position);
         let path = astar(
             &graph,
             self.get_node_from_pos(start_position),
             |finish| finish == finish_node,
-            |_e| 1,
-            |node| {
-                let from_pos = self.get_pos_from_node(node);
-                let to_pos = self.get_pos_from_node(finish_node);
-                from_pos.0.abs_diff(to_pos.0) + from_pos.1.abs_diff(to_pos.1)
-            },
+            |e| *e.weight(),
+            |node| self.remaining_cost_estimate(self.get_pos_from_node(node)),
         );
 
         if let Some(path) = path {
@@ -181,17 +351,123 @@ impl Game {
             None
         }
     }
+
+    pub fn shortest_path_dijkstra(&self, graph: &Graph<usize, usize>, start_position: Pos) -> Option<Vec<Pos>> {
+        let finish_node = self.get_node_from_pos(self.end_position);
+        // A* without a heuristic is Dijkstra, and keeps track of the path even when some steps cost nothing
+        let (_, nodes) = astar(
+            &graph,
+            self.get_node_from_pos(start_position),
+            |finish| finish == finish_node,
+            |e| *e.weight(),
+            |_| 0,
+        )?;
+        Some(nodes.into_iter().map(|node| self.get_pos_from_node(node)).collect())
+    }
+
+    pub fn shortest_path_astar(&self, graph: &Graph<usize, usize>, start_position: Pos) -> Option<Vec<Pos>> {
+        let finish_node = self.get_node_from_pos(self.end_position);
+        let (_, nodes) = astar(
+            &graph,
+            self.get_node_from_pos(start_position),
+            |finish| finish == finish_node,
+            |e| *e.weight(),
+            |node| self.remaining_cost_estimate(self.get_pos_from_node(node)),
+        )?;
+        Some(nodes.into_iter().map(|node| self.get_pos_from_node(node)).collect())
+    }
+
+    // whether `to` is the next square on some shortest path from `from`
+    fn on_shortest_path(&self, distances: &DistanceMap, from: Pos, to: Pos) -> bool {
+        match (distances.cost_and_steps(from), distances.cost_and_steps(to)) {
+            (Some((cost_from, steps_from)), Some((cost_to, steps_to))) => {
+                self.can_step(from, to) && steps_to + 1 == steps_from && cost_to + self.step_cost(from, to) == cost_from
+            }
+            _ => false,
+        }
+    }
+
+    // every cheapest path from `start` to the end with the fewest steps, generated lazily since there can be very many
+    pub fn all_shortest_paths(&self, start: Pos) -> impl Iterator<Item = Vec<Pos>> + '_ {
+        let distances = self.distances_to_end();
+        let mut stack = if distances.distance(start).is_some() {
+            vec![vec![start]]
+        } else {
+            Vec::new()
+        };
+        std::iter::from_fn(move || {
+            while let Some(path) = stack.pop() {
+                let current = *path.last().unwrap();
+                if current == self.end_position {
+                    return Some(path);
+                }
+                for (_, next) in self.get_edges_for_pos(current.0, current.1).into_iter().rev() {
+                    if self.on_shortest_path(&distances, current, next) {
+                        let mut longer = path.clone();
+                        longer.push(next);
+                        stack.push(longer);
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    // saturates instead of overflowing
+    pub fn count_shortest_paths(&self, start: Pos) -> u128 {
+        let distances = self.distances_to_end();
+        let mut squares: Vec<Pos> = (0..self.map_height())
+            .flat_map(|y| (0..self.map_width()).map(move |x| (x, y)))
+            .filter(|&pos| distances.distance(pos).is_some())
+            .collect();
+        squares.sort_by_key(|&pos| distances.cost_and_steps(pos));
+        let mut counts: HashMap<Pos, u128> = HashMap::new();
+        for pos in squares {
+            let count = if pos == self.end_position {
+                1
+            } else {
+                self.get_edges_for_pos(pos.0, pos.1)
+                    .into_iter()
+                    .filter(|&(_, next)| self.on_shortest_path(&distances, pos, next))
+                    .fold(0u128, |sum, (_, next)| sum.saturating_add(counts[&next]))
+            };
+            counts.insert(pos, count);
+        }
+        counts.get(&start).copied().unwrap_or(0)
+    }
+
+    // the height map with the path drawn on top of it, like the puzzle does
+    pub fn render_path(&self, path: &[Pos]) -> String {
+        let mut overlay = HashMap::from([(self.start_position, 'S'), (self.end_position, 'E')]);
+        for step in path.windows(2) {
+            let arrow = match (step[1].0 as i64 - step[0].0 as i64, step[1].1 as i64 - step[0].1 as i64) {
+                (0, -1) => '^',
+                (1, 0) => '>',
+                (0, 1) => 'v',
+                (-1, 0) => '<',
+                (-1, -1) | (1, 1) => '\\',
+                (1, -1) | (-1, 1) => '/',
+                _ => '?',
+            };
+            overlay.insert(step[0], arrow);
+        }
+        render_grid(self.map_width(), self.map_height(), |x, y| {
+            overlay.get(&(x, y)).copied().unwrap_or(self.map[y][x])
+        })
+    }
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 Sabqponm
 abcryxxl
 accszExk
 acctuvwj
 abdefghi"#;
-    let game = input.parse::<Game>()?;
+
+#[test]
+fn test() -> Result<(), Error> {
+    let game = EXAMPLE.parse::<Game>()?;
     assert_eq!((game.map_width(), game.map_height()), (8, 5));
     assert_eq!(game.start_position, (0, 0));
     assert_eq!(game.end_position, (5, 2));
@@ -199,11 +475,194 @@ abdefghi"#;
     assert_eq!(game.height_at(5, 2), 25);
     assert_eq!(game.height_at(7, 4), 'i' as usize - 'a' as usize);
     assert_eq!(game.minimum_steps_from(&game.get_graph(), game.start_position), Some(31));
-    assert_eq!(game.minimum_steps_from_any_a(), 29);
+    assert_eq!(game.minimum_steps_from_any_a(), Some(29));
 
     let game = std::fs::read_to_string("input/day12")?.parse::<Game>()?;
     assert_eq!(game.minimum_steps_from(&game.get_graph(), game.start_position), Some(517));
-    assert_eq!(game.minimum_steps_from_any_a(), 512);
+    assert_eq!(game.minimum_steps_from_any_a(), Some(512));
+
+    Ok(())
+}
+
+#[test]
+fn test_distances() -> Result<(), Error> {
+    let game = EXAMPLE.parse::<Game>()?;
+    let distances = game.distances_to_end();
+    assert_eq!(distances.distance(game.start_position), Some(31));
+    assert_eq!(distances.distance(game.end_position), Some(0));
+    assert_eq!(game.best_start(0), Some(((0, 4), 29)));
+    let path = distances.path_from(game.start_position).unwrap();
+    assert_eq!(path.len(), 32);
+    assert_eq!((path[0], path[31]), (game.start_position, game.end_position));
+    assert!(path.windows(2).all(|step| game.can_step(step[0], step[1])));
+    assert!(path
+        .windows(2)
+        .all(|step| step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1) == 1));
+
+    let game = "SbcdzE".parse::<Game>()?;
+    let distances = game.distances_to_end();
+    assert_eq!(distances.distance(game.start_position), None);
+    assert_eq!(distances.path_from(game.start_position), None);
+    assert_eq!(distances.distance((4, 0)), Some(1));
+    assert_eq!(game.best_start(0), None);
+    assert_eq!(game.minimum_steps_from_any_a(), None);
+
+    assert!("Sabc".parse::<Game>().is_err());
+    assert!("abcE".parse::<Game>().is_err());
+    assert!("".parse::<Game>().is_err());
+    assert!("SbcE\nab".parse::<Game>().is_err());
+
+    let game = std::fs::read_to_string("input/day12")?.parse::<Game>()?;
+    let distances = game.distances_to_end();
+    assert_eq!(distances.distance(game.start_position), Some(517));
+    assert_eq!(distances.path_from(game.start_position).map(|path| path.len()), Some(518));
+
+    Ok(())
+}
+
+#[test]
+fn test_paths() -> Result<(), Error> {
+    let game = EXAMPLE.parse::<Game>()?;
+    let graph = game.get_graph();
+    let path = game.shortest_path_dijkstra(&graph, game.start_position).unwrap();
+    assert_eq!(path.len(), 32);
+    assert_eq!(
+        game.shortest_path_astar(&graph, game.start_position).map(|path| path.len()),
+        Some(32)
+    );
+    assert_eq!(
+        game.render_path(&[(0, 0), (0, 1), (1, 1), (2, 1), (2, 2), (3, 2), (4, 2), (5, 2)]),
+        "vabqponm\n>>vryxxl\nac>>>Exk\nacctuvwj\nabdefghi\n"
+    );
+
+    let count = game.count_shortest_paths(game.start_position);
+    let paths: Vec<Vec<Pos>> = game.all_shortest_paths(game.start_position).collect();
+    assert_eq!(paths.len() as u128, count);
+    assert!(paths.contains(&path));
+    for path in paths.iter() {
+        assert_eq!(path.len(), 32);
+        assert!(path.windows(2).all(|step| game.can_step(step[0], step[1])));
+    }
+    assert_eq!(game.render_path(&paths[0]).lines().count(), 5);
+
+    let game = "SbcdzE".parse::<Game>()?;
+    assert_eq!(game.shortest_path_dijkstra(&game.get_graph(), game.start_position), None);
+    assert_eq!(game.all_shortest_paths(game.start_position).count(), 0);
+    assert_eq!(game.count_shortest_paths(game.start_position), 0);
+
+    let game = std::fs::read_to_string("input/day12")?.parse::<Game>()?;
+    let graph = game.get_graph();
+    assert_eq!(
+        game.shortest_path_dijkstra(&graph, game.start_position).map(|path| path.len()),
+        Some(518)
+    );
+    assert_eq!(
+        game.shortest_path_astar(&graph, game.start_position).map(|path| path.len()),
+        Some(518)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rules() -> Result<(), Error> {
+    let game = EXAMPLE.parse::<Game>()?;
+    assert!(!game.can_step((2, 0), (3, 0)));
+    let rules = ClimbingRules {
+        max_up: 25,
+        ..ClimbingRules::default()
+    };
+    let game = Game::new(EXAMPLE, rules, CostModel::Steps)?;
+    assert!(game.can_step((2, 0), (3, 0)));
+    assert_eq!(game.minimum_steps_from(&game.get_graph(), game.start_position), Some(7));
+
+    let rules = ClimbingRules {
+        max_down: Some(0),
+        ..ClimbingRules::default()
+    };
+    let game = Game::new(EXAMPLE, rules, CostModel::Steps)?;
+    assert!(!game.can_step((3, 0), (2, 0)));
+    assert_eq!(game.minimum_steps_from(&game.get_graph(), game.start_position), Some(31));
+
+    let rules = ClimbingRules {
+        diagonal: true,
+        ..ClimbingRules::default()
+    };
+    let game = Game::new(EXAMPLE, rules, CostModel::Steps)?;
+    let graph = game.get_graph();
+    assert_eq!(game.minimum_steps_from(&graph, game.start_position), Some(27));
+    assert_eq!(
+        game.shortest_path_astar(&graph, game.start_position).map(|path| path.len()),
+        Some(28)
+    );
+    let path = game.shortest_path_dijkstra(&graph, game.start_position).unwrap();
+    assert_eq!(path.len(), 28);
+    assert!(game.render_path(&path).starts_with("\\"));
+
+    // every route has to climb all 25 levels
+    let costs = CostModel::Energy {
+        per_step: 1,
+        per_height_up: 2,
+        per_height_down: 0,
+    };
+    let game = Game::new(EXAMPLE, ClimbingRules::default(), costs)?;
+    let graph = game.get_graph();
+    assert_eq!(game.minimum_steps_from(&graph, game.start_position), Some(81));
+    assert_eq!(game._minimum_steps_from_astar(&graph, game.start_position), Some(81));
+    let path = game.shortest_path_dijkstra(&graph, game.start_position).unwrap();
+    assert_eq!(path.windows(2).map(|step| game.step_cost(step[0], step[1])).sum::<usize>(), 81);
+    // the other queries use the same costs
+    let distances = game.distances_to_end();
+    assert_eq!(distances.distance(game.start_position), Some(81));
+    let path = distances.path_from(game.start_position).unwrap();
+    assert_eq!(path.windows(2).map(|step| game.step_cost(step[0], step[1])).sum::<usize>(), 81);
+    assert_eq!(game.best_start(0).map(|(_, cost)| cost), Some(79));
+    assert_eq!(game.minimum_steps_from_any_a(), Some(79));
+    let paths: Vec<Vec<Pos>> = game.all_shortest_paths(game.start_position).collect();
+    assert_eq!(paths.len() as u128, game.count_shortest_paths(game.start_position));
+    assert!(paths
+        .iter()
+        .all(|path| path.windows(2).map(|step| game.step_cost(step[0], step[1])).sum::<usize>() == 81));
+
+    // steps on level ground are free
+    let costs = CostModel::Energy {
+        per_step: 0,
+        per_height_up: 1,
+        per_height_down: 1,
+    };
+    let game = Game::new(EXAMPLE, ClimbingRules::default(), costs)?;
+    let graph = game.get_graph();
+    assert_eq!(game.minimum_steps_from(&graph, game.start_position), Some(25));
+    let path = game.shortest_path_astar(&graph, game.start_position).unwrap();
+    assert_eq!(path.windows(2).map(|step| game.step_cost(step[0], step[1])).sum::<usize>(), 25);
+    assert_eq!(game.distances_to_end().distance(game.start_position), Some(25));
+    // free steps do not make the number of shortest paths infinite, the fewest steps are taken
+    let paths: Vec<Vec<Pos>> = game.all_shortest_paths(game.start_position).collect();
+    assert_eq!(paths.len() as u128, game.count_shortest_paths(game.start_position));
+    assert!(paths.iter().all(|path| path.len() == 32));
+
+    let input = std::fs::read_to_string("input/day12")?;
+    let rules = ClimbingRules {
+        diagonal: true,
+        ..ClimbingRules::default()
+    };
+    let game = Game::new(&input, rules, CostModel::Steps)?;
+    let graph = game.get_graph();
+    assert_eq!(game.minimum_steps_from(&graph, game.start_position), Some(364));
+    assert_eq!(
+        game.shortest_path_astar(&graph, game.start_position).map(|path| path.len()),
+        Some(365)
+    );
+    let costs = CostModel::Energy {
+        per_step: 1,
+        per_height_up: 2,
+        per_height_down: 1,
+    };
+    let game = Game::new(&input, rules, costs)?;
+    let graph = game.get_graph();
+    assert_eq!(game.minimum_steps_from(&graph, game.start_position), Some(420));
+    assert_eq!(game._minimum_steps_from_astar(&graph, game.start_position), Some(420));
+    assert_eq!(game.distances_to_end().distance(game.start_position), Some(420));
 
     Ok(())
 }
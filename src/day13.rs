@@ -3,24 +3,177 @@
 // part2: Organize all of the packets into the correct order. What is the decoder key for the distress signal?
 
 use crate::error::Error;
-use serde_json::Value;
 use std::cmp::Ordering;
+use std::str::FromStr;
 
-type Packet = Vec<Value>;
+// equality is structural, so [2] and [[2]] differ even though the puzzle puts them in neither order, see Packet::compare
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Int(i64),
+    List(Vec<Packet>),
+}
 
 pub struct Pair {
     pub lhs: Packet,
     pub rhs: Packet,
 }
 
+// recursive descent over the input bytes, integers are parsed straight from slices of the input
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        Error::Parse(format!("{} at position {}: {}", reason, self.position, self.input))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn packet(&mut self) -> Result<Packet, Error> {
+        match self.peek() {
+            Some(b'[') => self.list(),
+            Some(b'-' | b'0'..=b'9') => self.int(),
+            _ => Err(self.error("expected '[' or an integer")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Packet, Error> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Packet::List(items));
+        }
+        loop {
+            items.push(self.packet()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Packet::List(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn int(&mut self) -> Result<Packet, Error> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.position += 1;
+        }
+        let digits = &self.input[start..self.position];
+        digits
+            .parse()
+            .map(Packet::Int)
+            .map_err(|_| self.error(&format!("invalid integer '{}'", digits)))
+    }
+}
+
+impl FromStr for Packet {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, position: 0 };
+        let packet = parser.packet()?;
+        if parser.position != s.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(packet)
+    }
+}
+
+impl std::fmt::Display for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packet::Int(value) => write!(f, "{}", value),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Packet {
+    // the puzzle's order, where an integer compared to a list is treated as a list holding only that integer.
+    // 2, [2] and [[2]] are all Equal here without being the same packet
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(lhs), Packet::Int(rhs)) => lhs.cmp(rhs),
+            (Packet::List(lhs), Packet::List(rhs)) => Packet::compare_lists(lhs, rhs),
+            (Packet::Int(_), Packet::List(rhs)) => Packet::compare_lists(std::slice::from_ref(self), rhs),
+            (Packet::List(lhs), Packet::Int(_)) => Packet::compare_lists(lhs, std::slice::from_ref(other)),
+        }
+    }
+
+    fn compare_lists(lhs: &[Packet], rhs: &[Packet]) -> Ordering {
+        for (lhs, rhs) in lhs.iter().zip(rhs) {
+            let ordering = lhs.compare(rhs);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        lhs.len().cmp(&rhs.len())
+    }
+
+    // breaks the ties of `compare`: an integer goes before a list, and lists compare item by item
+    fn compare_structure(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(lhs), Packet::Int(rhs)) => lhs.cmp(rhs),
+            (Packet::List(lhs), Packet::List(rhs)) => lhs
+                .iter()
+                .zip(rhs)
+                .map(|(lhs, rhs)| lhs.compare_structure(rhs))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or_else(|| lhs.len().cmp(&rhs.len())),
+            (Packet::Int(_), Packet::List(_)) => Ordering::Less,
+            (Packet::List(_), Packet::Int(_)) => Ordering::Greater,
+        }
+    }
+}
+
+// the puzzle's order, with packets it puts in neither order sorted by their structure so that Ord agrees with Eq.
+// 2 < [2] < [[2]], and all three are Equal for Packet::compare
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare(other).then_with(|| self.compare_structure(other))
+    }
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 pub fn read_packets(input: &str) -> Result<Vec<Packet>, Error> {
     let mut packets = Vec::new();
     for line in input.trim_start().trim_end().lines() {
         if line.trim_start().trim_end().is_empty() {
             continue;
         }
-        let packet: Packet = serde_json::from_str(line).unwrap();
-        packets.push(packet);
+        packets.push(line.trim_start().trim_end().parse()?);
     }
     Ok(packets)
 }
@@ -36,88 +189,123 @@ pub fn split_into_pairs(packets: Vec<Packet>) -> Vec<Pair> {
     pairs
 }
 
-fn is_end_of_array(array: &Vec<Value>, index: usize) -> bool {
-    array.len() < index + 1
+// why a pair compares the way it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Integers { lhs: i64, rhs: i64 },
+    LeftRanOut,
+    RightRanOut,
+    Identical,
 }
 
-fn compare_recursively(lhs: &Vec<Value>, rhs: &Vec<Value>) -> std::cmp::Ordering {
-    for i in 0..std::cmp::max(lhs.len(), rhs.len()) {
-        if is_end_of_array(lhs, i) {
-            return Ordering::Less;
-        }
-        if is_end_of_array(rhs, i) {
-            return Ordering::Greater;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    // indices from the outer list down to the first difference, an integer compared to a list counts as a list of one
+    pub path: Vec<usize>,
+    pub reason: Reason,
+}
+
+impl Diagnostic {
+    pub fn ordering(&self) -> Ordering {
+        match self.reason {
+            Reason::Integers { lhs, rhs } => lhs.cmp(&rhs),
+            Reason::LeftRanOut => Ordering::Less,
+            Reason::RightRanOut => Ordering::Greater,
+            Reason::Identical => Ordering::Equal,
         }
-        let a: &Value = &lhs[i];
-        let b: &Value = &rhs[i];
-        if a.is_number() && b.is_number() {
-            let a = a.as_u64().unwrap();
-            let b = b.as_u64().unwrap();
-            match a.cmp(&b) {
-                Ordering::Equal => {}
-                Ordering::Less => {
-                    return Ordering::Less;
-                }
-                Ordering::Greater => {
-                    return Ordering::Greater;
-                }
-            }
-        } else {
-            let result = if a.is_number() && b.is_array() {
-                compare_recursively(&vec![a.clone()], b.as_array().unwrap())
-            } else if a.is_array() && b.is_number() {
-                compare_recursively(a.as_array().unwrap(), &vec![b.clone()])
-            } else {
-                compare_recursively(a.as_array().unwrap(), b.as_array().unwrap())
-            };
-            match result {
-                Ordering::Equal => {}
-                Ordering::Less => {
-                    return Ordering::Less;
-                }
-                Ordering::Greater => {
-                    return Ordering::Greater;
-                }
-            }
+    }
+}
+
+fn first_difference(lhs: &Packet, rhs: &Packet, path: &mut Vec<usize>) -> Option<Reason> {
+    match (lhs, rhs) {
+        (Packet::Int(lhs), Packet::Int(rhs)) => (lhs != rhs).then_some(Reason::Integers { lhs: *lhs, rhs: *rhs }),
+        (Packet::List(lhs), Packet::List(rhs)) => first_difference_in_lists(lhs, rhs, path),
+        (Packet::Int(_), Packet::List(rhs)) => first_difference_in_lists(std::slice::from_ref(lhs), rhs, path),
+        (Packet::List(lhs), Packet::Int(_)) => first_difference_in_lists(lhs, std::slice::from_ref(rhs), path),
+    }
+}
+
+fn first_difference_in_lists(lhs: &[Packet], rhs: &[Packet], path: &mut Vec<usize>) -> Option<Reason> {
+    for index in 0..std::cmp::max(lhs.len(), rhs.len()) {
+        path.push(index);
+        let reason = match (lhs.get(index), rhs.get(index)) {
+            (None, _) => Some(Reason::LeftRanOut),
+            (_, None) => Some(Reason::RightRanOut),
+            (Some(lhs), Some(rhs)) => first_difference(lhs, rhs, path),
+        };
+        if reason.is_some() {
+            return reason;
         }
+        path.pop();
     }
-    Ordering::Equal
+    None
+}
+
+pub fn diagnose(pair: &Pair) -> Diagnostic {
+    let mut path = Vec::new();
+    let reason = first_difference(&pair.lhs, &pair.rhs, &mut path).unwrap_or(Reason::Identical);
+    Diagnostic { path, reason }
 }
 
 pub fn is_in_order(pair: &Pair) -> bool {
-    compare_recursively(&pair.lhs, &pair.rhs) == Ordering::Less
+    pair.lhs.compare(&pair.rhs) == Ordering::Less
 }
 
-pub fn sum_of_in_order_indices(pairs: &[Pair]) -> usize {
-    pairs
+pub struct OrderReport {
+    pub sum: usize,
+    // one per pair, in input order
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+pub fn sum_of_in_order_indices(pairs: &[Pair]) -> OrderReport {
+    let diagnostics: Vec<Diagnostic> = pairs.iter().map(diagnose).collect();
+    let sum = diagnostics
         .iter()
         .enumerate()
-        .filter(|(_index, pair)| is_in_order(pair))
-        .map(|(index, _pair)| index + 1)
-        .sum()
+        .filter(|(_index, diagnostic)| diagnostic.ordering() == Ordering::Less)
+        .map(|(index, _diagnostic)| index + 1)
+        .sum();
+    OrderReport { sum, diagnostics }
 }
 
 pub fn create_divider_packets() -> Vec<Packet> {
     read_packets("[[2]]\n[[6]]").unwrap()
 }
 
+// stable, so packets that compare equal keep their order
 pub fn sort_packets(packets: &mut [Packet]) {
-    packets.sort_by(compare_recursively);
+    packets.sort_by(Packet::compare);
 }
 
-pub fn decoder_key(packets: &[Packet]) -> usize {
-    let divider_packets = create_divider_packets();
-    packets
+// the product of the 1-based positions the dividers would have if they were appended to `packets` and everything was
+// sorted with sort_packets, counted without sorting. packets that compare equal to a divider, like [2] for [[2]], are
+// therefore placed before it, as are earlier dividers that compare equal to it
+pub fn decoder_key_with(packets: &[Packet], dividers: &[Packet]) -> usize {
+    dividers
         .iter()
         .enumerate()
-        .filter(|(_index, packet)| divider_packets.iter().any(|divider| packet == &divider))
-        .map(|(index, _pair)| index + 1)
+        .map(|(index, divider)| {
+            let packets_before = packets.iter().filter(|packet| packet.compare(divider) != Ordering::Greater).count();
+            let dividers_before = dividers
+                .iter()
+                .enumerate()
+                .filter(|&(other_index, other)| match other.compare(divider) {
+                    Ordering::Less => true,
+                    Ordering::Equal => other_index < index,
+                    Ordering::Greater => false,
+                })
+                .count();
+            packets_before + dividers_before + 1
+        })
         .product()
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+pub fn decoder_key(packets: &[Packet]) -> usize {
+    decoder_key_with(packets, &create_divider_packets())
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 [1,1,3,1,1]
 [1,1,5,1,1]
 
@@ -142,7 +330,9 @@ fn test() -> Result<(), Error> {
 [1,[2,[3,[4,[5,6,7]]]],8,9]
 [1,[2,[3,[4,[5,6,0]]]],8,9]"#;
 
-    let packets = read_packets(input)?;
+#[test]
+fn test() -> Result<(), Error> {
+    let packets = read_packets(EXAMPLE)?;
     let pairs = split_into_pairs(packets);
     assert!(is_in_order(&pairs[0]));
     assert!(is_in_order(&pairs[1]));
@@ -152,20 +342,109 @@ fn test() -> Result<(), Error> {
     assert!(is_in_order(&pairs[5]));
     assert!(!is_in_order(&pairs[6]));
     assert!(!is_in_order(&pairs[7]));
-    assert_eq!(sum_of_in_order_indices(&pairs), 13);
+    assert_eq!(sum_of_in_order_indices(&pairs).sum, 13);
     let packets = read_packets(&std::fs::read_to_string("input/day13")?)?;
     let pairs = split_into_pairs(packets);
-    assert_eq!(sum_of_in_order_indices(&pairs), 5605);
+    assert_eq!(sum_of_in_order_indices(&pairs).sum, 5605);
 
-    let mut packets = read_packets(input)?;
-    packets.append(&mut create_divider_packets());
-    sort_packets(&mut packets);
+    let packets = read_packets(EXAMPLE)?;
     assert_eq!(decoder_key(&packets), 140);
 
-    let mut packets = read_packets(&std::fs::read_to_string("input/day13")?)?;
-    packets.append(&mut create_divider_packets());
-    sort_packets(&mut packets);
+    let packets = read_packets(&std::fs::read_to_string("input/day13")?)?;
     assert_eq!(decoder_key(&packets), 24969);
 
     Ok(())
 }
+
+#[test]
+fn test_packet() -> Result<(), Error> {
+    let packet = "[1,[2,[3,[4,[5,6,7]]]],8,9]".parse::<Packet>()?;
+    assert_eq!(packet.to_string(), "[1,[2,[3,[4,[5,6,7]]]],8,9]");
+    assert_eq!("[]".parse::<Packet>()?.to_string(), "[]");
+    assert_eq!("[-3,[10],[]]".parse::<Packet>()?.to_string(), "[-3,[10],[]]");
+
+    assert!("[1.5]".parse::<Packet>().is_err());
+    assert!("[\"a\"]".parse::<Packet>().is_err());
+    assert!("[1,2".parse::<Packet>().is_err());
+    assert!("[1,]".parse::<Packet>().is_err());
+    assert!("[1]]".parse::<Packet>().is_err());
+    assert!("[-]".parse::<Packet>().is_err());
+
+    let parse = |s: &str| s.parse::<Packet>().unwrap();
+    assert_eq!(parse("[-1]").compare(&parse("[0]")), Ordering::Less);
+    assert_eq!(parse("[9]").compare(&parse("[[8,7,6]]")), Ordering::Greater);
+    assert_eq!(parse("[[1],[2,3,4]]").compare(&parse("[[1],4]")), Ordering::Less);
+    assert_eq!(parse("[[2]]").compare(&parse("2")), Ordering::Equal);
+    assert_eq!(parse("[[2]]").compare(&parse("[2]")), Ordering::Equal);
+    assert_ne!(parse("[[2]]"), parse("[2]"));
+    assert_eq!(parse("[[2]]"), parse("[[2]]"));
+    assert_eq!(parse("[[2]]").compare(&parse("[[2],[]]")), Ordering::Less);
+
+    // Ord follows the puzzle and only breaks its ties
+    assert!(parse("[-1]") < parse("[0]"));
+    assert!(parse("[9]") > parse("[[8,7,6]]"));
+    assert!(parse("[[1],[2,3,4]]") < parse("[[1],4]"));
+    assert!(parse("2") < parse("[2]") && parse("[2]") < parse("[[2]]"));
+    assert!(parse("[[2]]") < parse("[3]"));
+    assert_eq!(parse("[[2]]").cmp(&parse("[[2]]")), Ordering::Equal);
+    let mut packets = read_packets("[[6]]\n[[2]]\n2\n[]\n[2]\n[1,[3]]")?;
+    assert_eq!(packets.iter().max(), Some(&parse("[[6]]")));
+    packets.sort();
+    let sorted: Vec<String> = packets.iter().map(|packet| packet.to_string()).collect();
+    assert_eq!(sorted, vec!["[]", "[1,[3]]", "2", "[2]", "[[2]]", "[[6]]"]);
+    assert!(packets.windows(2).all(|pair| pair[0].compare(&pair[1]) != Ordering::Greater));
+
+    let input = std::fs::read_to_string("input/day13")?;
+    for line in input.lines().filter(|line| !line.is_empty()) {
+        assert_eq!(line.parse::<Packet>()?.to_string(), line);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_decoder() -> Result<(), Error> {
+    let pairs = split_into_pairs(read_packets(EXAMPLE)?);
+    let report = sum_of_in_order_indices(&pairs);
+    assert_eq!(report.diagnostics.len(), 8);
+    let expected = [
+        (vec![2], Reason::Integers { lhs: 3, rhs: 5 }),
+        (vec![1, 0], Reason::Integers { lhs: 2, rhs: 4 }),
+        (vec![0, 0], Reason::Integers { lhs: 9, rhs: 8 }),
+        (vec![3], Reason::LeftRanOut),
+        (vec![3], Reason::RightRanOut),
+        (vec![0], Reason::LeftRanOut),
+        (vec![0, 0], Reason::RightRanOut),
+        (vec![1, 1, 1, 1, 2], Reason::Integers { lhs: 7, rhs: 0 }),
+    ];
+    for ((diagnostic, pair), (path, reason)) in report.diagnostics.iter().zip(pairs.iter()).zip(expected) {
+        assert_eq!(diagnostic, &Diagnostic { path, reason });
+        assert_eq!(diagnostic.ordering(), pair.lhs.compare(&pair.rhs));
+    }
+    let same = split_into_pairs(read_packets("[[2]]\n[2]")?);
+    assert_eq!(diagnose(&same[0]).reason, Reason::Identical);
+
+    // [2] and 6 compare equal to the dividers without being them, and [] is in the example. appended dividers sort last among equals
+    let mut packets = read_packets(&format!("{}\n[2]\n6", EXAMPLE))?;
+    let dividers = read_packets("[[6]]\n[[2]]\n[]")?;
+    let key = decoder_key_with(&packets, &dividers);
+    packets.extend(dividers.iter().cloned());
+    sort_packets(&mut packets);
+    let positions: Vec<usize> = dividers
+        .iter()
+        .map(|divider| packets.iter().rposition(|packet| packet == divider).unwrap() + 1)
+        .collect();
+    assert_eq!(key, positions.iter().product());
+    assert_eq!(decoder_key(&read_packets(&format!("{}\n[2]", EXAMPLE))?), 11 * 15);
+    assert_eq!(decoder_key_with(&[], &dividers), 6);
+
+    let packets = read_packets(&std::fs::read_to_string("input/day13")?)?;
+    let pairs = split_into_pairs(packets.clone());
+    let report = sum_of_in_order_indices(&pairs);
+    for (diagnostic, pair) in report.diagnostics.iter().zip(pairs.iter()) {
+        assert_eq!(diagnostic.ordering(), pair.lhs.compare(&pair.rhs));
+    }
+    assert_eq!(decoder_key_with(&packets, &create_divider_packets()), 24969);
+
+    Ok(())
+}
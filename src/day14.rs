@@ -3,107 +3,246 @@
 // part2: (sand line) How many units of sand come to rest?
 
 use crate::error::Error;
+use crate::utils::render_grid;
+use std::collections::HashMap;
 
-type Point = (usize, usize);
+type Point = (i64, i64);
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Object {
     Nothing,
     Wall,
     Sand,
 }
 
+// a horizontal line of rock, spanning the whole cave when `x_range` is None
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Floor {
+    pub y: i64,
+    pub x_range: Option<(i64, i64)>,
+}
+
+impl Floor {
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        y == self.y
+            && match self.x_range {
+                Some((from, to)) => (from..=to).contains(&x),
+                None => true,
+            }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grain {
+    Settled(Point),
+    // the first point below everything solid
+    Escaped(Point),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Escape {
+    pub source: Point,
+    pub position: Point,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Pouring {
+    // grains settled from each source
+    pub settled: Vec<usize>,
+    // at most one per source, since a source stops once its sand escapes
+    pub escapes: Vec<Escape>,
+}
+
+impl Pouring {
+    pub fn total(&self) -> usize {
+        self.settled.iter().sum()
+    }
+}
+
+// only walls and sand are stored, so the cave has no bounds and grows as sand piles up
 pub struct Map {
-    pub map: Vec<Object>,
-    pub start_x: usize,
-    pub start_y: usize,
-    pub end_x: usize,
-    pub end_y: usize,
-    pub sand_line: Option<usize>,
+    pub cells: HashMap<Point, Object>,
+    pub floors: Vec<Floor>,
+    // the lowest y of any wall or floor, below that sand falls forever
+    pub bottom: i64,
 }
 
 impl Map {
-    pub fn width(&self) -> usize {
-        self.end_x - self.start_x + 1
+    pub fn new(walls: &[Point], floors: Vec<Floor>) -> Self {
+        let cells: HashMap<Point, Object> = walls.iter().map(|&point| (point, Object::Wall)).collect();
+        let bottom = walls.iter().map(|p| p.1).chain(floors.iter().map(|f| f.y)).max().unwrap_or(0);
+        Map { cells, floors, bottom }
     }
 
-    pub fn height(&self) -> usize {
-        self.end_y - self.start_y + 1
+    pub fn set_object_at(&mut self, x: i64, y: i64, object: Object) {
+        if object == Object::Nothing {
+            self.cells.remove(&(x, y));
+        } else {
+            self.cells.insert((x, y), object);
+        }
     }
 
-    pub fn set_object_at(&mut self, x: usize, y: usize, object: Object) {
-        let x = x - self.start_x;
-        let y = y - self.start_y;
-        self.map[y * (self.end_x - self.start_x + 1) + x] = object;
+    // None is the abyss below the bottom of the cave
+    pub fn get_object_at(&self, x: i64, y: i64) -> Option<Object> {
+        if let Some(object) = self.cells.get(&(x, y)) {
+            return Some(*object);
+        }
+        if self.floors.iter().any(|floor| floor.contains(x, y)) {
+            return Some(Object::Wall);
+        }
+        if y > self.bottom {
+            return None;
+        }
+        Some(Object::Nothing)
     }
 
-    pub fn get_object_at(&self, x: usize, y: usize) -> Option<Object> {
-        if let Some(sand_line) = self.sand_line {
-            if y == sand_line {
-                return Some(Object::Sand);
-            }
-        } else {
-            if x < self.start_x {
-                return None;
+    // lets one grain fall from `from` until it comes to rest or leaves the cave
+    pub fn drop_grain(&mut self, from: Point) -> Grain {
+        self.fall(&mut vec![from])
+    }
+
+    // lets one grain fall from the end of `path`, pushing every position it moves to and popping the one it settles on.
+    // what is left is the path of the next grain up to the point where it can take a different route
+    fn fall(&mut self, path: &mut Vec<Point>) -> Grain {
+        let (mut sand_x, mut sand_y) = *path.last().unwrap();
+        if self.get_object_at(sand_x, sand_y).is_none() {
+            return Grain::Escaped((sand_x, sand_y));
+        }
+        'grain: loop {
+            let alternatives = [(0, 1), (-1, 1), (1, 1)];
+            for alternative in alternatives.iter() {
+                let test_x = sand_x + alternative.0;
+                let test_y = sand_y + alternative.1;
+                match self.get_object_at(test_x, test_y) {
+                    None => {
+                        return Grain::Escaped((test_x, test_y));
+                    }
+                    Some(Object::Nothing) => {
+                        sand_x = test_x;
+                        sand_y = test_y;
+                        path.push((sand_x, sand_y));
+                        continue 'grain;
+                    }
+                    Some(Object::Wall) | Some(Object::Sand) => {}
+                };
             }
+            self.set_object_at(sand_x, sand_y, Object::Sand);
+            path.pop();
+            return Grain::Settled((sand_x, sand_y));
+        }
+    }
 
-            if x > self.end_x {
-                return None;
+    // the sources take turns dropping one grain each, until every source is blocked or has sand escaping from it
+    pub fn pour(&mut self, sources: &[Point]) -> Pouring {
+        let mut pouring = Pouring {
+            settled: vec![0; sources.len()],
+            escapes: Vec::new(),
+        };
+        let mut paths: Vec<Vec<Point>> = sources.iter().map(|&source| vec![source]).collect();
+        let mut active = vec![true; sources.len()];
+        while active.contains(&true) {
+            for (index, &source) in sources.iter().enumerate() {
+                if !active[index] {
+                    continue;
+                }
+                // sand from the other sources can land on the remembered path
+                let path = &mut paths[index];
+                while path
+                    .last()
+                    .is_some_and(|&(x, y)| matches!(self.get_object_at(x, y), Some(Object::Wall) | Some(Object::Sand)))
+                {
+                    path.pop();
+                }
+                if path.is_empty() {
+                    active[index] = false;
+                    continue;
+                }
+                match self.fall(path) {
+                    Grain::Settled(_) => {
+                        pouring.settled[index] += 1;
+                    }
+                    Grain::Escaped(position) => {
+                        pouring.escapes.push(Escape { source, position });
+                        active[index] = false;
+                    }
+                }
             }
+        }
+        pouring
+    }
 
-            if y > self.end_y {
-                return None;
+    // how many grains poured from `source` still come to rest, counted without simulating them: on a floor that spans
+    // the whole cave sand fills every empty cell it can reach by falling, and those are found one row at a time.
+    // None when no such floor is below the source, since then sand may escape
+    pub fn count_settling(&self, source: Point) -> Option<usize> {
+        let floor = self
+            .floors
+            .iter()
+            .filter(|floor| floor.x_range.is_none() && floor.y > source.1)
+            .map(|floor| floor.y)
+            .min()?;
+        let is_empty = |x: i64, y: i64| self.get_object_at(x, y) == Some(Object::Nothing);
+        if !is_empty(source.0, source.1) {
+            return Some(0);
+        }
+        // reachable cells of the current row, covering source.x - depth ..= source.x + depth
+        let mut reachable = vec![true];
+        let mut count = 1;
+        for y in source.1 + 1..floor {
+            let depth = y - source.1;
+            let was_reachable = |x: i64| {
+                let index = x - (source.0 - depth + 1);
+                index >= 0 && reachable.get(index as usize).copied().unwrap_or(false)
+            };
+            let next: Vec<bool> = (source.0 - depth..=source.0 + depth)
+                .map(|x| (was_reachable(x - 1) || was_reachable(x) || was_reachable(x + 1)) && is_empty(x, y))
+                .collect();
+            let reached = next.iter().filter(|&&cell| cell).count();
+            if reached == 0 {
+                break;
             }
+            count += reached;
+            reachable = next;
         }
-        Some(self.map[(y - self.start_y) * self.width() + (x - self.start_x)])
+        Some(count)
     }
 
     pub fn pour_from(&mut self, from: Point) -> usize {
-        let mut num_settled = 0;
-        'pouring: loop {
-            let mut sand_x = from.0;
-            let mut sand_y = from.1;
-            'grain: loop {
-                let alternatives = [(0, 1), (-1, 1), (1, 1)];
-                for alternative in alternatives.iter() {
-                    let test_x = (sand_x as i32 + alternative.0) as usize;
-                    let test_y = (sand_y as i32 + alternative.1) as usize;
-                    match self.get_object_at(test_x, test_y) {
-                        None => {
-                            break 'pouring;
-                        }
-                        Some(object) => match object {
-                            Object::Nothing => {
-                                sand_x = test_x;
-                                sand_y = test_y;
-                                continue 'grain;
-                            }
-                            Object::Wall => {}
-                            Object::Sand => {}
-                        },
-                    };
-                }
-                num_settled += 1;
-                self.set_object_at(sand_x, sand_y, Object::Sand);
-                if (sand_x, sand_y) == from {
-                    break 'pouring;
-                } else {
-                    break 'grain;
-                }
-            }
+        self.pour(&[from]).total()
+    }
+
+    // the smallest box around everything stored, plus the floors within its columns
+    pub fn render(&self) -> String {
+        if self.cells.is_empty() {
+            return String::new();
         }
-        num_settled
+        let min_x = self.cells.keys().map(|p| p.0).min().unwrap();
+        let max_x = self.cells.keys().map(|p| p.0).max().unwrap();
+        let min_y = self.cells.keys().map(|p| p.1).min().unwrap().min(0);
+        let width = (max_x - min_x + 1) as usize;
+        let height = (self.bottom - min_y + 1) as usize;
+        render_grid(width, height, |x, y| match self.get_object_at(min_x + x as i64, min_y + y as i64) {
+            Some(Object::Wall) => '#',
+            Some(Object::Sand) => 'o',
+            _ => '.',
+        })
     }
 }
 
-pub fn generate_map(s: &str, part2: bool) -> Result<Map, Error> {
+pub fn parse_walls(s: &str) -> Result<Vec<Point>, Error> {
     let mut point_list: Vec<Point> = Vec::new();
     for line in s.trim_start().trim_end().lines() {
         let mut last_point: Option<Point> = None;
         for point in line.split(" -> ") {
-            let mut points = point.split(',');
-            let current_point: Point = (points.next().unwrap().parse()?, points.next().unwrap().parse()?);
+            let (x, y) = point
+                .trim()
+                .split_once(',')
+                .ok_or_else(|| Error::Parse(format!("invalid point: {}", point)))?;
+            let current_point: Point = (x.parse()?, y.parse()?);
             if let Some(last_point) = last_point {
+                if last_point.0 != current_point.0 && last_point.1 != current_point.1 {
+                    return Err(Error::Parse(format!("diagonal wall: {}", line)));
+                }
                 for y in std::cmp::min(last_point.1, current_point.1)..=std::cmp::max(last_point.1, current_point.1) {
                     for x in std::cmp::min(last_point.0, current_point.0)..=std::cmp::max(last_point.0, current_point.0) {
                         point_list.push((x, y));
@@ -113,53 +252,37 @@ pub fn generate_map(s: &str, part2: bool) -> Result<Map, Error> {
             last_point = Some(current_point)
         }
     }
-    let start_y = 0;
-    let end_y = point_list.iter().map(|p| p.1).max().unwrap() + 2;
-    let start_x = point_list.iter().map(|p| p.0).min().unwrap() - end_y;
-    let end_x = point_list.iter().map(|p| p.0).max().unwrap() + end_y;
-    let map: Vec<Object> = vec![Object::Nothing; (end_y - start_y + 1) * (end_x - start_x + 1)];
-    let mut map = Map {
-        map,
-        start_x,
-        start_y,
-        end_x,
-        end_y,
-        sand_line: if part2 { Some(end_y) } else { None },
-    };
-    for point in point_list {
-        map.set_object_at(point.0, point.1, Object::Wall);
+    Ok(point_list)
+}
+
+pub fn generate_map(s: &str, part2: bool) -> Result<Map, Error> {
+    let walls = parse_walls(s)?;
+    let mut floors = Vec::new();
+    if part2 {
+        let lowest = walls.iter().map(|p| p.1).max().unwrap_or(0);
+        floors.push(Floor {
+            y: lowest + 2,
+            x_range: None,
+        });
     }
-    Ok(map)
+    Ok(Map::new(&walls, floors))
 }
 
 pub fn dump(map: &Map) {
-    for y in 0..map.height() {
-        for x in 0..map.width() {
-            match map.get_object_at(x, y).unwrap() {
-                Object::Nothing => {
-                    print!(".");
-                }
-                Object::Wall => {
-                    print!("#");
-                }
-                Object::Sand => {
-                    print!("o");
-                }
-            }
-        }
-        println!();
-    }
+    print!("{}", map.render());
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 498,4 -> 498,6 -> 496,6
 503,4 -> 502,4 -> 502,9 -> 494,9"#;
-    let mut map = generate_map(input, false)?;
+
+#[test]
+fn test() -> Result<(), Error> {
+    let mut map = generate_map(EXAMPLE, false)?;
     assert_eq!(map.pour_from((500, 0)), 24);
 
-    let mut map = generate_map(input, true)?;
+    let mut map = generate_map(EXAMPLE, true)?;
     assert_eq!(map.pour_from((500, 0)), 93);
 
     let mut map = generate_map(&std::fs::read_to_string("input/day14")?, false)?;
@@ -170,3 +293,109 @@ fn test() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_cave() -> Result<(), Error> {
+    let mut map = generate_map(EXAMPLE, false)?;
+    let pouring = map.pour(&[(500, 0)]);
+    assert_eq!(pouring.total(), 24);
+    assert_eq!(
+        pouring.escapes,
+        vec![Escape {
+            source: (500, 0),
+            position: (493, 10)
+        }]
+    );
+    assert_eq!(
+        map.render(),
+        "..........\n..........\n......o...\n.....ooo..\n....#ooo##\n...o#ooo#.\n..###ooo#.\n....oooo#.\n.o.ooooo#.\n#########.\n"
+    );
+
+    // the floor can be anywhere, also far to the left of zero
+    let mut map = Map::new(&[], vec![Floor { y: 3, x_range: None }]);
+    assert_eq!(map.pour_from((-1000, 0)), 9);
+    assert_eq!(map.get_object_at(-1002, 2), Some(Object::Sand));
+    assert_eq!(map.get_object_at(-1000, 3), Some(Object::Wall));
+
+    // a short ledge holds a single grain before sand rolls off its left end
+    let mut map = Map::new(
+        &[],
+        vec![Floor {
+            y: 2,
+            x_range: Some((0, 2)),
+        }],
+    );
+    let pouring = map.pour(&[(1, 0)]);
+    assert_eq!(pouring.settled, vec![1]);
+    assert_eq!(
+        pouring.escapes,
+        vec![Escape {
+            source: (1, 0),
+            position: (-1, 3)
+        }]
+    );
+
+    // two sources share a floor, the second one sits inside the first one's pile and is buried by it
+    let mut map = Map::new(&[], vec![Floor { y: 4, x_range: None }]);
+    let pouring = map.pour(&[(0, 0), (2, 2)]);
+    assert_eq!(pouring.total(), 1 + 3 + 5 + 7);
+    assert!(pouring.settled.iter().all(|&settled| settled > 0));
+    assert!(pouring.escapes.is_empty());
+    assert_eq!(map.get_object_at(2, 2), Some(Object::Sand));
+
+    // sand rolls off a single block of rock, and a source below the cave loses its sand straight away
+    let mut map = Map::new(&[(0, 1)], vec![]);
+    let pouring = map.pour(&[(0, 0), (5, 5)]);
+    assert_eq!(pouring.settled, vec![0, 0]);
+    assert_eq!(
+        pouring.escapes,
+        vec![
+            Escape {
+                source: (0, 0),
+                position: (-1, 2)
+            },
+            Escape {
+                source: (5, 5),
+                position: (5, 5)
+            }
+        ]
+    );
+
+    assert!(parse_walls("1,1 -> 2,2").is_err());
+    assert!(parse_walls("1 -> 2,2").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_fast_pouring() -> Result<(), Error> {
+    assert_eq!(generate_map(EXAMPLE, false)?.count_settling((500, 0)), None);
+    let map = generate_map(EXAMPLE, true)?;
+    assert_eq!(map.count_settling((500, 0)), Some(93));
+    assert_eq!(map.count_settling((500, 10)), Some(1));
+    assert_eq!(map.count_settling((500, 11)), None);
+
+    // sand already in the cave is taken into account
+    let mut map = generate_map(EXAMPLE, true)?;
+    assert_eq!(map.pour(&[(497, 8)]).total(), 1);
+    assert_eq!(map.count_settling((500, 0)), Some(92));
+    assert_eq!(map.pour_from((500, 0)), 92);
+
+    let walls = parse_walls(&std::fs::read_to_string("input/day14")?)?;
+    let floor = Floor {
+        y: walls.iter().map(|p| p.1).max().unwrap() + 2,
+        x_range: None,
+    };
+    let mut map = Map::new(&walls, vec![floor]);
+    assert_eq!(map.count_settling((500, 0)), Some(28744));
+    assert_eq!(map.pour_from((500, 0)), 28744);
+
+    // a deep empty cave fills up a triangle
+    let mut map = Map::new(&[], vec![Floor { y: 300, x_range: None }]);
+    assert_eq!(map.count_settling((0, 0)), Some(300 * 300));
+    assert_eq!(map.pour_from((0, 0)), 300 * 300);
+    let map = Map::new(&walls, vec![Floor { y: 2000, x_range: None }]);
+    assert!(map.count_settling((500, 0)).unwrap() < 2000 * 2000);
+
+    Ok(())
+}
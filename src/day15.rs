@@ -3,11 +3,9 @@
 // part2: Find the only possible position for the distress beacon. What is its tuning frequency?
 
 use crate::error::Error;
-use rayon::iter::IntoParallelRefIterator;
-use rayon::iter::ParallelIterator;
-use std::sync::{Arc, Mutex};
 
 type Point = (i64, i64);
+pub type Interval = (i64, i64);
 
 pub fn load_line(line: &str) -> Result<(Point, Point), Error> {
     use text_io::try_scan;
@@ -38,99 +36,169 @@ pub fn manhattan_distance(p1: Point, p2: Point) -> i64 {
     (p1.0.abs_diff(p2.0) + p1.1.abs_diff(p2.1)) as i64
 }
 
-pub fn num_no_beacon_points_at_row(input: &str, row: i64) -> Result<usize, Error> {
-    let mut coverage_at_row: Vec<Point> = Vec::with_capacity(10_000_000); // min 7_672_418
-    let (sensors, mut beacons) = load_sensors_and_beacons(input)?;
-    for index in 0..sensors.len() {
-        let sensor = sensors[index];
-        let beacon = beacons[index];
-        let distance = manhattan_distance(sensor, beacon);
-        let diff_to_row = sensor.1.abs_diff(row) as i64;
-        let left_to_sides = distance - diff_to_row;
-        if left_to_sides > 0 {
-            coverage_at_row.push((sensor.0, row));
-            for x in 1..=left_to_sides {
-                coverage_at_row.push(((sensor.0 - x), row));
-                coverage_at_row.push(((sensor.0 + x), row));
-            }
+// the x ranges (inclusive) seen by at least one sensor on `row`, sorted and merged so none of them overlap or touch
+pub fn row_coverage(sensors: &[Point], beacons: &[Point], row: i64) -> Vec<Interval> {
+    let mut intervals: Vec<Interval> = sensors
+        .iter()
+        .zip(beacons.iter())
+        .filter_map(|(&sensor, &beacon)| {
+            let left_to_sides = manhattan_distance(sensor, beacon) - sensor.1.abs_diff(row) as i64;
+            (left_to_sides >= 0).then_some((sensor.0 - left_to_sides, sensor.0 + left_to_sides))
+        })
+        .collect();
+    intervals.sort();
+
+    let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for (from, to) in intervals {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 + 1 => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
         }
     }
+    merged
+}
 
-    beacons.sort();
-    beacons.dedup();
+pub fn num_no_beacon_points_at_row(input: &str, row: i64) -> Result<usize, Error> {
+    let (sensors, beacons) = load_sensors_and_beacons(input)?;
+    let coverage = row_coverage(&sensors, &beacons, row);
 
-    coverage_at_row.sort();
-    coverage_at_row.dedup();
+    // sensors and beacons are always covered, and they are the points known not to be an unknown beacon
+    let mut occupied: Vec<i64> = sensors.iter().chain(beacons.iter()).filter(|p| p.1 == row).map(|p| p.0).collect();
+    occupied.sort();
+    occupied.dedup();
 
-    Ok(coverage_at_row
-        .into_iter()
-        .filter(|p| !sensors.contains(p) && !beacons.contains(p))
-        .count())
+    let covered: i64 = coverage.iter().map(|(from, to)| to - from + 1).sum();
+    Ok(covered as usize - occupied.len())
 }
 
+pub const TUNING_MULTIPLIER: i64 = 4_000_000;
+
 pub fn tuning_frequency(p: Point) -> i64 {
-    p.0 * 4000000i64 + p.1
+    tuning_frequency_with(p, TUNING_MULTIPLIER)
 }
 
-pub fn find_distress_beacon(input: &str) -> Result<Option<Point>, Error> {
-    let (sensors, beacons) = load_sensors_and_beacons(input)?;
-    let mut vision = Vec::with_capacity(sensors.len());
-    let mut max_x = 0;
-    let mut max_y = 0;
-    for index in 0..sensors.len() {
-        let sensor = sensors[index];
-        let beacon = beacons[index];
-        let sensor_vision = manhattan_distance(sensor, beacon);
-        vision.push((sensor, sensor_vision));
-        max_x = std::cmp::max(max_x, sensor.0);
-        max_y = std::cmp::max(max_y, sensor.1);
+pub fn tuning_frequency_with(p: Point, multiplier: i64) -> i64 {
+    p.0 * multiplier + p.1
+}
+
+// the part of the map to search, corners included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl SearchBox {
+    pub fn square(size: i64) -> Self {
+        SearchBox {
+            min: (0, 0),
+            max: (size, size),
+        }
     }
+}
 
-    max_x = std::cmp::min(max_x, 4000000);
-    max_y = std::cmp::min(max_y, 4000000);
-
-    let count = Arc::new(Mutex::new(0i64));
-    let found: Option<Point> = None;
-    let found = Arc::new(Mutex::new(found));
-    let ys: Vec<i64> = (0..=max_y).collect();
-    let _found = ys.par_iter().find_any(|y| {
-        for x in 0..=max_x {
-            // can anyone see this point?
-            let visible = vision.iter().any(|(sensor_location, vision)| {
-                let distance = manhattan_distance(*sensor_location, (x, **y));
-                distance <= *vision
-            });
+// a rectangle in rotated coordinates u = x + y and v = x - y, corners included.
+// a sensor's diamond is a square there, so what it leaves uncovered is a handful of rectangles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rotated {
+    u: (i64, i64),
+    v: (i64, i64),
+}
 
-            if !visible {
-                println!("found: {} {}", x, y);
-                *found.lock().unwrap() = Some((x, **y));
-                return true;
-            }
+impl Rotated {
+    fn around(sensor: Point, distance: i64) -> Self {
+        let (u, v) = (sensor.0 + sensor.1, sensor.0 - sensor.1);
+        Rotated {
+            u: (u - distance, u + distance),
+            v: (v - distance, v + distance),
         }
+    }
 
-        let mut lock = count.lock().unwrap();
-        *lock += 1;
-        if *lock % 1000 == 0 {
-            println!("{}%", (*lock as f64 / max_y as f64) * 100f64);
+    fn subtract(&self, other: &Rotated, remaining: &mut Vec<Rotated>) {
+        if other.u.0 > self.u.1 || other.u.1 < self.u.0 || other.v.0 > self.v.1 || other.v.1 < self.v.0 {
+            remaining.push(*self);
+            return;
+        }
+        // the full-height strips left and right of `other`, then what is above and below it in between
+        if self.u.0 < other.u.0 {
+            remaining.push(Rotated {
+                u: (self.u.0, other.u.0 - 1),
+                v: self.v,
+            });
+        }
+        if self.u.1 > other.u.1 {
+            remaining.push(Rotated {
+                u: (other.u.1 + 1, self.u.1),
+                v: self.v,
+            });
+        }
+        let u = (self.u.0.max(other.u.0), self.u.1.min(other.u.1));
+        if self.v.0 < other.v.0 {
+            remaining.push(Rotated {
+                u,
+                v: (self.v.0, other.v.0 - 1),
+            });
         }
+        if self.v.1 > other.v.1 {
+            remaining.push(Rotated {
+                u,
+                v: (other.v.1 + 1, self.v.1),
+            });
+        }
+    }
 
-        false
-    });
+    // the grid points inside both this rectangle and the search box
+    fn points_within(&self, search_box: &SearchBox) -> Vec<Point> {
+        let ((min_x, min_y), (max_x, max_y)) = (search_box.min, search_box.max);
+        let mut points = Vec::new();
+        // only the u for which the box leaves some v
+        let u_from = self.u.0.max(min_x + min_y).max(self.v.0 + 2 * min_y).max(2 * min_x - self.v.1);
+        let u_to = self.u.1.min(max_x + max_y).min(self.v.1 + 2 * max_y).min(2 * max_x - self.v.0);
+        for u in u_from..=u_to {
+            let v_from = self.v.0.max(2 * min_x - u).max(u - 2 * max_y);
+            let v_to = self.v.1.min(2 * max_x - u).min(u - 2 * min_y);
+            // x and y are only whole numbers when u and v are both even or both odd
+            let v_from = v_from + (u - v_from).rem_euclid(2);
+            for v in (v_from..=v_to).step_by(2) {
+                points.push(((u + v) / 2, (u - v) / 2));
+            }
+        }
+        points
+    }
+}
 
-    let found = *found.lock().unwrap();
-    if let Some(point) = found {
-        Ok(Some(point))
-    } else {
-        Ok(None)
+// every point in the box no sensor can see, in the order of x and then y. only the corners of the sensors' diamonds are
+// looked at, so the cost does not depend on the size of the box, apart from the points that are returned
+pub fn uncovered_points(sensors: &[Point], beacons: &[Point], search_box: &SearchBox) -> Vec<Point> {
+    let ((min_x, min_y), (max_x, max_y)) = (search_box.min, search_box.max);
+    if min_x > max_x || min_y > max_y {
+        return Vec::new();
     }
+    let mut uncovered = vec![Rotated {
+        u: (min_x + min_y, max_x + max_y),
+        v: (min_x - max_y, max_x - min_y),
+    }];
+    for (&sensor, &beacon) in sensors.iter().zip(beacons.iter()) {
+        let covered = Rotated::around(sensor, manhattan_distance(sensor, beacon));
+        let mut remaining = Vec::with_capacity(uncovered.len());
+        for rectangle in uncovered.iter() {
+            rectangle.subtract(&covered, &mut remaining);
+        }
+        uncovered = remaining;
+    }
+    let mut points: Vec<Point> = uncovered.iter().flat_map(|rectangle| rectangle.points_within(search_box)).collect();
+    points.sort();
+    points
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    assert_eq!(manhattan_distance((8, 7), (2, 10)), 9);
-    assert_eq!(tuning_frequency((14, 11)), 56000011);
+// all candidates for the distress beacon, there should be exactly one
+pub fn find_distress_beacon(input: &str, search_box: &SearchBox) -> Result<Vec<Point>, Error> {
+    let (sensors, beacons) = load_sensors_and_beacons(input)?;
+    Ok(uncovered_points(&sensors, &beacons, search_box))
+}
 
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
     Sensor at x=2, y=18: closest beacon is at x=-2, y=15
     Sensor at x=9, y=16: closest beacon is at x=10, y=16
     Sensor at x=13, y=2: closest beacon is at x=15, y=3
@@ -147,24 +215,107 @@ fn test() -> Result<(), Error> {
     Sensor at x=20, y=1: closest beacon is at x=15, y=3
     "#;
 
-    let n = num_no_beacon_points_at_row(input, 9)?;
+#[test]
+fn test() -> Result<(), Error> {
+    assert_eq!(manhattan_distance((8, 7), (2, 10)), 9);
+    assert_eq!(tuning_frequency((14, 11)), 56000011);
+
+    let n = num_no_beacon_points_at_row(EXAMPLE, 9)?;
     assert_eq!(n, 25);
-    let n = num_no_beacon_points_at_row(input, 10)?;
+    let n = num_no_beacon_points_at_row(EXAMPLE, 10)?;
     assert_eq!(n, 26);
-    let n = num_no_beacon_points_at_row(input, 11)?;
+    let n = num_no_beacon_points_at_row(EXAMPLE, 11)?;
     assert_eq!(n, 27);
     let n = num_no_beacon_points_at_row(&std::fs::read_to_string("input/day15")?, 2000000)?;
     println!("n: {}", n);
     assert_eq!(n, 4737443);
 
-    let p = find_distress_beacon(input)?;
-    assert_eq!(p, Some((14, 11)));
-    assert_eq!(tuning_frequency(p.unwrap()), 56000011);
+    let p = find_distress_beacon(EXAMPLE, &SearchBox::square(20))?;
+    assert_eq!(p, vec![(14, 11)]);
+    assert_eq!(tuning_frequency(p[0]), 56000011);
+
+    let p = find_distress_beacon(&std::fs::read_to_string("input/day15")?, &SearchBox::square(4000000))?;
+    assert_eq!(p, vec![(2870615, 2818989)]);
+    assert_eq!(tuning_frequency(p[0]), 11482462818989);
+
+    Ok(())
+}
+
+#[test]
+fn test_row_coverage() -> Result<(), Error> {
+    let (sensors, beacons) = load_sensors_and_beacons(EXAMPLE)?;
+    assert_eq!(row_coverage(&sensors, &beacons, 10), vec![(-2, 24)]);
+    assert_eq!(row_coverage(&sensors, &beacons, 11), vec![(-3, 13), (15, 25)]);
+    assert_eq!(row_coverage(&sensors, &beacons, 1_000_000), vec![]);
+
+    // a sensor just reaching the row covers a single point, and touching ranges are merged
+    assert_eq!(row_coverage(&[(0, 0)], &[(0, 3)], 3), vec![(0, 0)]);
+    assert_eq!(row_coverage(&[(0, 0), (3, 0)], &[(1, 0), (4, 0)], 0), vec![(-1, 4)]);
+    assert_eq!(row_coverage(&[(0, 0), (4, 0)], &[(1, 0), (5, 0)], 0), vec![(-1, 1), (3, 5)]);
+
+    // huge ranges far from the origin cost no more than small ones
+    let sensors = [(-5_000_000_000, 7_000_000_000), (5_000_000_000, 7_000_000_000)];
+    let beacons = [(0, 7_000_000_000), (0, 7_000_000_000)];
+    assert_eq!(
+        row_coverage(&sensors, &beacons, 7_000_000_000),
+        vec![(-10_000_000_000, 10_000_000_000)]
+    );
+    assert_eq!(
+        row_coverage(&sensors, &beacons, 11_000_000_000),
+        vec![(-6_000_000_000, -4_000_000_000), (4_000_000_000, 6_000_000_000)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_uncovered_points() -> Result<(), Error> {
+    let (sensors, beacons) = load_sensors_and_beacons(EXAMPLE)?;
+
+    // compare with checking every point, in boxes that cut through diamonds and leave gaps
+    for search_box in [
+        SearchBox::square(20),
+        SearchBox {
+            min: (-3, -2),
+            max: (27, 25),
+        },
+        SearchBox {
+            min: (10, 9),
+            max: (16, 12),
+        },
+        SearchBox {
+            min: (14, 11),
+            max: (14, 11),
+        },
+    ] {
+        let mut expected = Vec::new();
+        for x in search_box.min.0..=search_box.max.0 {
+            for y in search_box.min.1..=search_box.max.1 {
+                let seen = sensors
+                    .iter()
+                    .zip(beacons.iter())
+                    .any(|(&sensor, &beacon)| manhattan_distance(sensor, (x, y)) <= manhattan_distance(sensor, beacon));
+                if !seen {
+                    expected.push((x, y));
+                }
+            }
+        }
+        assert_eq!(uncovered_points(&sensors, &beacons, &search_box), expected);
+    }
 
-    let p = find_distress_beacon(&std::fs::read_to_string("input/day15")?)?;
-    assert!(p.is_some());
-    //found at (2870615, 2818989) (55.06158740517171%)
-    assert_eq!(tuning_frequency(p.unwrap()), 11482462818989);
+    assert_eq!(
+        uncovered_points(&[], &[], &SearchBox::square(1)),
+        vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+    );
+    assert_eq!(
+        uncovered_points(&[(1, 1)], &[(1, 2)], &SearchBox::square(2)),
+        vec![(0, 0), (0, 2), (2, 0), (2, 2)]
+    );
+    assert_eq!(
+        uncovered_points(&sensors, &beacons, &SearchBox { min: (5, 5), max: (4, 4) }),
+        vec![]
+    );
+    assert_eq!(tuning_frequency_with((14, 11), 20), 291);
 
     Ok(())
 }
@@ -39,150 +39,338 @@ pub fn load_valves(input: &str) -> Result<HashMap<String, Valve>, Error> {
     Ok(valves)
 }
 
-fn recursively_find_max_pressure(
-    visited: Vec<String>,
-    total_actions: String,
-    valves: &HashMap<String, Valve>,
-    opened: Vec<String>,
-    minute: i64,
-    max_minutes: i64,
-    total_pressure_release: i64,
-    max_total_pressure_release: &mut i64,
-) -> Option<(i64, Vec<String>, String)> {
-    if minute > max_minutes {
-        return None;
+// a set of flowing valves, one bit per index in a ValveGraph
+pub type ValveSet = u64;
+
+// the valves worth going to, with the minutes it takes to walk between any two of them.
+// the flowing valves come first, sorted by name, so every subset of them is a ValveSet below 1 << num_flowing.
+// the start valve comes after them unless it is flowing itself
+#[derive(Debug, Clone)]
+pub struct ValveGraph {
+    pub names: Vec<String>,
+    pub flow_rates: Vec<i64>,
+    pub num_flowing: usize,
+    pub start: usize,
+    // None where no tunnels connect the two valves
+    pub distances: Vec<Vec<Option<i64>>>,
+}
+
+impl ValveGraph {
+    pub fn new(valves: &HashMap<String, Valve>, start: &str) -> Result<Self, Error> {
+        if !valves.contains_key(start) {
+            return Err(Error::General(format!("unknown start valve: {}", start)));
+        }
+        let mut all_names: Vec<&str> = valves.keys().map(|name| name.as_str()).collect();
+        all_names.sort();
+        let all_index: HashMap<&str, usize> = all_names.iter().enumerate().map(|(index, &name)| (name, index)).collect();
+
+        // floyd-warshall over every valve
+        let n = all_names.len();
+        let mut all_distances = vec![vec![None; n]; n];
+        for (index, name) in all_names.iter().enumerate() {
+            all_distances[index][index] = Some(0);
+            for path in valves[*name].paths.iter() {
+                let to = *all_index
+                    .get(path.name.as_str())
+                    .ok_or_else(|| Error::General(format!("tunnel from {} to unknown valve {}", name, path.name)))?;
+                all_distances[index][to] = Some(1);
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    if let (Some(via_lhs), Some(via_rhs)) = (all_distances[i][k], all_distances[k][j]) {
+                        if !matches!(all_distances[i][j], Some(direct) if direct <= via_lhs + via_rhs) {
+                            all_distances[i][j] = Some(via_lhs + via_rhs);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut kept: Vec<&str> = all_names.iter().copied().filter(|name| valves[*name].flow_rate > 0).collect();
+        let num_flowing = kept.len();
+        if num_flowing > ValveSet::BITS as usize {
+            return Err(Error::General(format!("too many flowing valves: {}", num_flowing)));
+        }
+        let start_index = match kept.iter().position(|&name| name == start) {
+            Some(index) => index,
+            None => {
+                kept.push(start);
+                num_flowing
+            }
+        };
+        let distances = kept
+            .iter()
+            .map(|from| kept.iter().map(|to| all_distances[all_index[from]][all_index[to]]).collect())
+            .collect();
+        Ok(ValveGraph {
+            names: kept.iter().map(|name| name.to_string()).collect(),
+            flow_rates: kept.iter().map(|name| valves[*name].flow_rate).collect(),
+            num_flowing,
+            start: start_index,
+            distances,
+        })
     }
 
-    if *max_total_pressure_release < total_pressure_release {
-        *max_total_pressure_release = total_pressure_release;
-        println!("max: {}, actions: {}", max_total_pressure_release, total_actions);
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
     }
 
-    if minute == max_minutes {
-        return Some((total_pressure_release, visited, total_actions));
+    pub fn distance(&self, from: usize, to: usize) -> Option<i64> {
+        self.distances[from][to]
     }
 
-    if opened.len() == valves.len() - 1 {
-        // println!("bajs detected (all valves opened, no need to run around)");
-        return None;
+    pub fn bit(index: usize) -> ValveSet {
+        1 << index
     }
 
-    if visited.len() > 4
-        && visited[visited.len() - 1] == visited[visited.len() - 3]
-        && visited[visited.len() - 2] == visited[visited.len() - 4]
-    {
-        // println!("bajs detected (loop");
-        return None;
+    pub fn all_flowing(&self) -> ValveSet {
+        if self.num_flowing == ValveSet::BITS as usize {
+            ValveSet::MAX
+        } else {
+            Self::bit(self.num_flowing) - 1
+        }
     }
 
-    let current_valve_name = visited.last().unwrap();
+    pub fn names_in(&self, set: ValveSet) -> Vec<&str> {
+        (0..self.num_flowing)
+            .filter(|&index| set & Self::bit(index) != 0)
+            .map(|index| self.names[index].as_str())
+            .collect()
+    }
+}
 
-    // println!("{}", &total_actions);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    // one minute per tunnel
+    Move { to: String, minutes: i64 },
+    // the valve releases pressure from the minute after this one
+    Open { valve: String, minute: i64 },
+}
 
-    let mut results = Vec::new();
-    results.push(Some((total_pressure_release, visited.clone(), total_actions.to_string())));
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub pressure: i64,
+    pub steps: Vec<Step>,
+}
 
-    let valve_details = &valves[current_valve_name];
+// remembers the best pressure for every (valve, minutes left, valves still closed) it has seen
+pub struct Solver<'a> {
+    graph: &'a ValveGraph,
+    memo: HashMap<(usize, i64, ValveSet), i64>,
+}
 
-    for path in &valve_details.paths {
-        if minute > max_minutes {
-            continue;
+impl<'a> Solver<'a> {
+    pub fn new(graph: &'a ValveGraph) -> Self {
+        Solver {
+            graph,
+            memo: HashMap::new(),
         }
-        {
-            // open (if possible or necessary)
-            if minute < max_minutes && valve_details.flow_rate > 0 && !opened.contains(current_valve_name) {
-                let total_actions = format!("{}|{}-open({})", total_actions, minute, &current_valve_name,);
-                let total_pressure_release = total_pressure_release + valve_details.flow_rate * (30 - minute);
-                results.push(Some((total_pressure_release, visited.clone(), total_actions.to_string())));
-                let minute = minute + 1;
-                if minute < max_minutes {
-                    let mut opened = opened.clone();
-                    opened.push(current_valve_name.to_string());
-                    let mut visited = visited.clone();
-                    visited.push(path.name.to_string());
-                    let result = recursively_find_max_pressure(
-                        visited,
-                        format!("{}|{}-move({})", &total_actions, minute, &path.name),
-                        valves,
-                        opened,
-                        minute + 1,
-                        max_minutes,
-                        total_pressure_release,
-                        max_total_pressure_release,
-                    );
-                    results.push(result);
-                }
-            }
+    }
+
+    fn minutes_left_after_opening(&self, from: usize, to: usize, remaining: i64) -> Option<i64> {
+        let left = remaining - self.graph.distance(from, to)? - 1;
+        (left > 0).then_some(left)
+    }
+
+    // the most pressure still to be released standing at `position` with `remaining` minutes, opening only valves in `closed`
+    pub fn best(&mut self, position: usize, remaining: i64, closed: ValveSet) -> i64 {
+        if let Some(&pressure) = self.memo.get(&(position, remaining, closed)) {
+            return pressure;
         }
-        {
-            if visited.len() > 2 && visited[visited.len() - 2] == path.name {
-                // println!("bajs detected (going back with no opening)");
+        let mut best = 0;
+        for next in 0..self.graph.num_flowing {
+            if closed & ValveGraph::bit(next) == 0 {
                 continue;
             }
-            // don't open
-            let mut visited = visited.clone();
-            visited.push(path.name.to_string());
-            let result = recursively_find_max_pressure(
-                visited,
-                format!("{}|{}-move({})", &total_actions, minute, &path.name),
-                valves,
-                opened.clone(),
-                minute + 1,
-                max_minutes,
-                total_pressure_release,
-                max_total_pressure_release,
-            );
-            results.push(result);
+            if let Some(left) = self.minutes_left_after_opening(position, next, remaining) {
+                let pressure = self.graph.flow_rates[next] * left + self.best(next, left, closed & !ValveGraph::bit(next));
+                best = best.max(pressure);
+            }
         }
+        self.memo.insert((position, remaining, closed), best);
+        best
     }
 
-    let mut results: Vec<(i64, Vec<String>, String)> = results.into_iter().flatten().collect();
-
-    if results.is_empty() {
-        return None;
+    // the best plan from the start valve that only opens valves in `allowed`, ties go to the valve with the lowest index
+    pub fn plan(&mut self, minutes: i64, allowed: ValveSet) -> Plan {
+        let graph = self.graph;
+        let mut position = graph.start;
+        let mut remaining = minutes;
+        let mut closed = allowed & graph.all_flowing();
+        let pressure = self.best(position, remaining, closed);
+        let mut steps = Vec::new();
+        let mut still_to_release = pressure;
+        'steps: while still_to_release > 0 {
+            for next in 0..graph.num_flowing {
+                if closed & ValveGraph::bit(next) == 0 {
+                    continue;
+                }
+                if let Some(left) = self.minutes_left_after_opening(position, next, remaining) {
+                    let released = graph.flow_rates[next] * left;
+                    if released + self.best(next, left, closed & !ValveGraph::bit(next)) == still_to_release {
+                        let distance = graph.distance(position, next).unwrap();
+                        if distance > 0 {
+                            steps.push(Step::Move {
+                                to: graph.names[next].to_string(),
+                                minutes: distance,
+                            });
+                        }
+                        steps.push(Step::Open {
+                            valve: graph.names[next].to_string(),
+                            minute: minutes - left,
+                        });
+                        position = next;
+                        remaining = left;
+                        closed &= !ValveGraph::bit(next);
+                        still_to_release -= released;
+                        continue 'steps;
+                    }
+                }
+            }
+            unreachable!("no step releases the remaining pressure");
+        }
+        Plan { pressure, steps }
     }
+}
+
+pub fn max_pressure_from(valves: &HashMap<String, Valve>, start: &str, minutes: i64) -> Result<Plan, Error> {
+    let graph = ValveGraph::new(valves, start)?;
+    Ok(Solver::new(&graph).plan(minutes, graph.all_flowing()))
+}
+
+pub fn max_pressure(valves: HashMap<String, Valve>, minutes: i64) -> Result<Option<i64>, Error> {
+    Ok(Some(max_pressure_from(&valves, "AA", minutes)?.pressure))
+}
 
-    results.sort_by(|a, b| b.0.cmp(&a.0));
+#[cfg(test)]
+const EXAMPLE: &str = r#"
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II"#;
 
-    let best = &results[0];
+#[test]
+fn test() -> Result<(), Error> {
+    let valves = load_valves(EXAMPLE)?;
+    assert_eq!(max_pressure(valves, 30)?, Some(1651));
 
-    Some((best.0, best.1.clone(), best.2.clone()))
+    let valves = load_valves(&std::fs::read_to_string("input/day16")?)?;
+    assert_eq!(max_pressure(valves, 30)?, Some(2359));
+
+    Ok(())
 }
 
-pub fn max_pressure(valves: HashMap<String, Valve>, minutes: i64) -> Result<Option<i64>, Error> {
-    let mut max_total_pressure_release = 0;
-    let result = recursively_find_max_pressure(
-        vec!["AA".to_string()],
-        "0-start|".to_string(),
-        &valves,
-        Vec::new(),
-        1,
-        minutes,
-        0,
-        &mut max_total_pressure_release,
+#[test]
+fn test_valve_graph() -> Result<(), Error> {
+    let valves = load_valves(EXAMPLE)?;
+    let graph = ValveGraph::new(&valves, "AA")?;
+    assert_eq!(graph.names, vec!["BB", "CC", "DD", "EE", "HH", "JJ", "AA"]);
+    assert_eq!(graph.flow_rates, vec![13, 2, 20, 3, 22, 21, 0]);
+    assert_eq!((graph.num_flowing, graph.start), (6, 6));
+    let index = |name| graph.index_of(name).unwrap();
+    assert_eq!(graph.distance(index("AA"), index("DD")), Some(1));
+    assert_eq!(graph.distance(index("AA"), index("HH")), Some(5));
+    assert_eq!(graph.distance(index("JJ"), index("HH")), Some(7));
+    assert_eq!(graph.distance(index("HH"), index("JJ")), Some(7));
+    assert_eq!(graph.distance(index("CC"), index("CC")), Some(0));
+    assert_eq!(graph.all_flowing(), 0b111111);
+    assert_eq!(
+        graph.names_in(ValveGraph::bit(index("DD")) | ValveGraph::bit(index("BB"))),
+        vec!["BB", "DD"]
     );
-    println!("result: {:?}", result);
-    Ok(result.map(|x| x.0))
+
+    // a flowing start valve is not added twice, and valves cut off from the rest have no distance
+    let valves = load_valves("Valve AA has flow rate=5; tunnel leads to valve BB\nValve BB has flow rate=0; tunnel leads to valve AA\nValve CC has flow rate=1; tunnel leads to valve CC")?;
+    let graph = ValveGraph::new(&valves, "AA")?;
+    assert_eq!(graph.names, vec!["AA", "CC"]);
+    assert_eq!(graph.start, 0);
+    assert_eq!(graph.distance(0, 1), None);
+    assert!(ValveGraph::new(&valves, "ZZ").is_err());
+
+    let valves = load_valves(&std::fs::read_to_string("input/day16")?)?;
+    let graph = ValveGraph::new(&valves, "AA")?;
+    assert_eq!(graph.names.len(), graph.num_flowing + 1);
+    for from in 0..graph.names.len() {
+        for to in 0..graph.names.len() {
+            assert_eq!(graph.distance(from, to), graph.distance(to, from));
+            assert!(graph.distance(from, to).is_some());
+        }
+    }
+
+    Ok(())
 }
 
-// #[test]
-// fn test() -> Result<(), Error> {
-//     let input = r#"
-// Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
-// Valve BB has flow rate=13; tunnels lead to valves CC, AA
-// Valve CC has flow rate=2; tunnels lead to valves DD, BB
-// Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
-// Valve EE has flow rate=3; tunnels lead to valves FF, DD
-// Valve FF has flow rate=0; tunnels lead to valves EE, GG
-// Valve GG has flow rate=0; tunnels lead to valves FF, HH
-// Valve HH has flow rate=22; tunnel leads to valve GG
-// Valve II has flow rate=0; tunnels lead to valves AA, JJ
-// Valve JJ has flow rate=21; tunnel leads to valve II"#;
-//     let valves = load_valves(input)?;
-//     assert_eq!(max_pressure(valves, 30)?, Some(1651));
-//
-//     // let valves = load_valves(&std::fs::read_to_string("input/day16")?)?;
-//     // assert_eq!(max_pressure(valves, 30)?, Some(2359)); // 74 minuter, no optimizations
-//
-//     Ok(())
-// }
+#[test]
+fn test_plan() -> Result<(), Error> {
+    let valves = load_valves(EXAMPLE)?;
+    let plan = max_pressure_from(&valves, "AA", 30)?;
+    assert_eq!(plan.pressure, 1651);
+    let opened: Vec<(&str, i64)> = plan
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Open { valve, minute } => Some((valve.as_str(), *minute)),
+            Step::Move { .. } => None,
+        })
+        .collect();
+    // the same order as in the puzzle
+    assert_eq!(opened, vec![("DD", 2), ("BB", 5), ("JJ", 9), ("HH", 17), ("EE", 21), ("CC", 24)]);
+    assert_eq!(
+        plan.steps[..2],
+        [
+            Step::Move {
+                to: "DD".to_string(),
+                minutes: 1
+            },
+            Step::Open {
+                valve: "DD".to_string(),
+                minute: 2
+            }
+        ]
+    );
+    let walked: i64 = plan
+        .steps
+        .iter()
+        .map(|step| match step {
+            Step::Move { minutes, .. } => *minutes,
+            Step::Open { .. } => 1,
+        })
+        .sum();
+    assert_eq!(walked, 24);
+
+    // the time budget and the start valve are honoured
+    assert_eq!(
+        max_pressure_from(&valves, "AA", 0)?,
+        Plan {
+            pressure: 0,
+            steps: vec![]
+        }
+    );
+    assert_eq!(max_pressure_from(&valves, "AA", 2)?.pressure, 0);
+    assert_eq!(max_pressure_from(&valves, "AA", 3)?.pressure, 20);
+    let plan = max_pressure_from(&valves, "HH", 3)?;
+    assert_eq!(plan.pressure, 2 * 22);
+    assert_eq!(
+        plan.steps,
+        vec![Step::Open {
+            valve: "HH".to_string(),
+            minute: 1
+        }]
+    );
+
+    // only some of the valves
+    let graph = ValveGraph::new(&valves, "AA")?;
+    let allowed = ValveGraph::bit(graph.index_of("BB").unwrap()) | ValveGraph::bit(graph.index_of("CC").unwrap());
+    let plan = Solver::new(&graph).plan(30, allowed);
+    assert_eq!(plan.pressure, 13 * 28 + 2 * 26);
+
+    Ok(())
+}
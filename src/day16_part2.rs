@@ -2,247 +2,122 @@
 // part1: Work out the steps to release the most pressure in 30 minutes. What is the most pressure you can release?
 // part2: With you and an elephant working together for 26 minutes, what is the most pressure you could release?
 
-use crate::day16::Valve;
+use crate::day16::{Plan, Solver, Valve, ValveGraph, ValveSet};
 use crate::error::Error;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Action<'a> {
-    Start,
-    Open(&'a str, i64),
-    MoveTo(&'a str, i64),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamPlan {
+    pub pressure: i64,
+    // one per agent, every valve is opened by at most one of them
+    pub agents: Vec<Plan>,
 }
 
-fn find_possible_actions<'a, 'b>(
-    valves: &'a HashMap<String, Valve>,
-    minute: i64,
-    opened: &'b [&'a str],
-    last_action: &Action<'a>,
-) -> Vec<Action<'a>> {
-    let current_value: &str = match last_action {
-        Action::Start => "AA",
-        Action::Open(valve, _) => valve,
-        Action::MoveTo(valve, _) => valve,
-    };
-    let mut possible_actions = Vec::with_capacity(6);
-    let valve_details = &valves[current_value];
-    if !opened.contains(&current_value) {
-        let valve_details = &valves[current_value];
-        if valve_details.flow_rate > 0 {
-            let increase = valve_details.flow_rate * (26 - minute);
-            possible_actions.push(Action::Open(current_value, increase));
+fn visit(graph: &ValveGraph, position: usize, remaining: i64, opened: ValveSet, pressure: i64, best: &mut [i64]) {
+    best[opened as usize] = best[opened as usize].max(pressure);
+    for next in 0..graph.num_flowing {
+        if opened & ValveGraph::bit(next) != 0 {
+            continue;
+        }
+        if let Some(distance) = graph.distance(position, next) {
+            let left = remaining - distance - 1;
+            if left > 0 {
+                let opened = opened | ValveGraph::bit(next);
+                visit(graph, next, left, opened, pressure + graph.flow_rates[next] * left, best);
+            }
         }
     }
-    for path in &valve_details.paths {
-        possible_actions.push(Action::MoveTo(&path.name, 1));
-    }
-    possible_actions
 }
 
-struct End {
-    max_open: usize,
-    max_minutes: i64,
-}
+// splitting the valves between agents looks at every pair of disjoint sets, which is 3^n of them
+pub const MAX_SPLIT_VALVES: usize = 16;
 
-#[derive(Hash, Eq, PartialEq)]
-struct State<'a> {
-    me_last_state: Action<'a>,
-    elephant_last_state: Action<'a>,
-    minute_me: i64,
-    minute_elephant: i64,
-}
-
-fn recursively_find_max_pressure<'a, 'b>(
-    total_actions: &'b mut Vec<(Action<'a>, Action<'a>)>,
-    valves: &'a HashMap<String, Valve>,
-    visited: &mut HashMap<State<'a>, i64>,
-    opened: &'b mut Vec<&'a str>,
-    minute_me: i64,
-    minute_elephant: i64,
-    end: &End,
-    mut total_pressure_release: i64,
-) -> Option<i64> {
-    let last_action = total_actions.last().unwrap();
-
-    if let Action::Open(_, increase) = &last_action.0 {
-        total_pressure_release += increase;
-    }
-    if let Action::Open(_, increase) = &last_action.1 {
-        total_pressure_release += increase;
+// the most pressure one agent can release opening no valves outside each set, indexed by ValveSet
+pub fn best_per_subset(graph: &ValveGraph, minutes: i64) -> Result<Vec<i64>, Error> {
+    if graph.num_flowing > MAX_SPLIT_VALVES {
+        return Err(Error::General(format!(
+            "too many flowing valves to split: {} (at most {})",
+            graph.num_flowing, MAX_SPLIT_VALVES
+        )));
     }
-
-    let entry = visited
-        .entry(State {
-            me_last_state: last_action.0.clone(),
-            elephant_last_state: last_action.1.clone(),
-            minute_me,
-            minute_elephant,
-        })
-        .or_insert(total_pressure_release);
-    if *entry > total_pressure_release {
-        return None;
-    } else {
-        *entry = total_pressure_release;
+    let mut best = vec![0; 1 << graph.num_flowing];
+    visit(graph, graph.start, minutes, 0, 0, &mut best);
+    // a set is at least as good as any of its subsets
+    for index in 0..graph.num_flowing {
+        for set in 0..best.len() {
+            if set & (1 << index) != 0 {
+                best[set] = best[set].max(best[set & !(1 << index)]);
+            }
+        }
     }
+    Ok(best)
+}
 
-    let mut num_opened = 0;
-    if let Action::Open(valve, _) = &last_action.0 {
-        opened.push(valve);
-        num_opened += 1;
+// splits the valves between the agents: every agent but the last takes a subset of what the ones after it
+// have not taken yet. ties go to the first subset found, so the result is always the same
+pub fn max_pressure_with_agents(valves: &HashMap<String, Valve>, start: &str, agents: usize, minutes: i64) -> Result<TeamPlan, Error> {
+    let graph = ValveGraph::new(valves, start)?;
+    if agents == 0 {
+        return Ok(TeamPlan {
+            pressure: 0,
+            agents: Vec::new(),
+        });
     }
-    if let Action::Open(valve, _) = &last_action.1 {
-        opened.push(valve);
-        num_opened += 1;
+    // nothing to split, so any number of valves will do
+    if agents == 1 {
+        let plan = Solver::new(&graph).plan(minutes, graph.all_flowing());
+        return Ok(TeamPlan {
+            pressure: plan.pressure,
+            agents: vec![plan],
+        });
     }
-
-    let mut results = Vec::with_capacity(6 * 6);
-
-    results.push(Some(total_pressure_release));
-
-    if opened.len() < end.max_open && minute_me < end.max_minutes && minute_elephant < end.max_minutes {
-        let my_possible_actions = find_possible_actions(valves, minute_me, opened, &last_action.0);
-        let elephant_possible_actions = find_possible_actions(valves, minute_elephant, opened, &last_action.1);
-        let mut possible_actions: Vec<(Action, Action)> = Vec::with_capacity(6 * 6);
-
-        for my_action in &my_possible_actions {
-            for elephant_action in &elephant_possible_actions {
-                if let Action::Open(lhs, _) = my_action {
-                    if let Action::Open(rhs, _) = elephant_action {
-                        if lhs == rhs {
-                            continue;
-                        }
-                    }
+    let alone = best_per_subset(&graph, minutes)?;
+
+    // team[k][set] is the best k + 1 agents can do with the valves in set, and taken[k - 1][set] is what the first of
+    // them opens. the last agent takes whatever is left
+    let mut team = vec![alone.clone()];
+    let mut taken: Vec<Vec<ValveSet>> = Vec::with_capacity(agents - 1);
+    for k in 1..agents {
+        let mut best = vec![0; alone.len()];
+        let mut best_taken = vec![0; alone.len()];
+        for set in 0..alone.len() {
+            let mut subset = set;
+            loop {
+                let pressure = alone[subset] + team[k - 1][set & !subset];
+                if pressure > best[set] {
+                    best[set] = pressure;
+                    best_taken[set] = subset as ValveSet;
                 }
-
-                if !possible_actions.contains(&(elephant_action.clone(), my_action.clone())) {
-                    let new_action = (my_action.clone(), elephant_action.clone());
-                    //println!("adding new_action: {:?}", &new_action);
-                    possible_actions.push(new_action);
+                if subset == 0 {
+                    break;
                 }
+                subset = (subset - 1) & set;
             }
         }
-
-        possible_actions.shuffle(&mut thread_rng());
-
-        for action_pair in possible_actions {
-            if invalid_move(total_actions, &action_pair) {
-                continue;
-            }
-
-            let minute_increase_me = get_action_cost(&action_pair.0);
-
-            if minute_me + minute_increase_me > end.max_minutes {
-                continue;
-            }
-
-            let minute_increase_elephant = get_action_cost(&action_pair.1);
-
-            if minute_elephant + minute_increase_elephant > end.max_minutes {
-                continue;
-            }
-
-            total_actions.push(action_pair);
-            let result = recursively_find_max_pressure(
-                total_actions,
-                valves,
-                visited,
-                opened,
-                minute_me + minute_increase_me,
-                minute_elephant + minute_increase_elephant,
-                end,
-                total_pressure_release,
-            );
-            total_actions.pop();
-            results.push(result);
-        }
+        team.push(best);
+        taken.push(best_taken);
     }
 
-    if num_opened > 0 {
-        opened.truncate(opened.len() - num_opened);
-    }
-
-    let mut results: Vec<i64> = results.into_iter().flatten().collect();
-
-    if results.is_empty() {
-        return None;
-    }
-
-    results.sort_by(|a, b| b.cmp(a));
-
-    let best = &results[0];
-
-    Some(*best)
-}
-
-fn get_action_cost(action: &Action) -> i64 {
-    match action {
-        Action::Start => {
-            panic!("internal error");
-        }
-        Action::Open(_, _) => 1,
-        Action::MoveTo(_, cost) => *cost,
+    let mut solver = Solver::new(&graph);
+    let mut remaining = graph.all_flowing();
+    let mut plans = Vec::with_capacity(agents);
+    for k in (0..agents).rev() {
+        let subset = if k == 0 { remaining } else { taken[k - 1][remaining as usize] };
+        plans.push(solver.plan(minutes, subset));
+        remaining &= !subset;
     }
+    Ok(TeamPlan {
+        pressure: team[agents - 1][graph.all_flowing() as usize],
+        agents: plans,
+    })
 }
 
 pub fn max_pressure(valves: HashMap<String, Valve>, max_minutes: i64) -> Result<Option<i64>, Error> {
-    let result = recursively_find_max_pressure(
-        &mut vec![(Action::Start, Action::Start)],
-        &valves,
-        &mut HashMap::new(),
-        &mut Vec::new(),
-        1,
-        1,
-        &End {
-            max_open: valves.values().filter(|v| v.flow_rate > 0).count(),
-            max_minutes,
-        },
-        0,
-    );
-    Ok(result)
-}
-
-pub fn invalid_move(total_actions: &[(Action, Action)], action: &(Action, Action)) -> bool {
-    for check in total_actions.iter().rev() {
-        match &check.0 {
-            Action::Open(_, _) => {
-                break;
-            }
-            Action::MoveTo(valve1, _) => {
-                if let Action::MoveTo(valve2, _) = &action.0 {
-                    if valve1 == valve2 {
-                        return true;
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    for check in total_actions.iter().rev() {
-        match &check.1 {
-            Action::Open(_, _) => {
-                break;
-            }
-            Action::MoveTo(valve1, _) => {
-                if let Action::MoveTo(valve2, _) = &action.1 {
-                    if valve1 == valve2 {
-                        //println!("idiot_move 1: {:?}, {:?}", action, total_actions);
-                        return true;
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    false
+    Ok(Some(max_pressure_with_agents(&valves, "AA", 2, max_minutes)?.pressure))
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
     Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
     Valve BB has flow rate=13; tunnels lead to valves CC, AA
     Valve CC has flow rate=2; tunnels lead to valves DD, BB
@@ -254,7 +129,9 @@ fn test() -> Result<(), Error> {
     Valve II has flow rate=0; tunnels lead to valves AA, JJ
     Valve JJ has flow rate=21; tunnel leads to valve II"#;
 
-    let valves = crate::day16::load_valves(input)?;
+#[test]
+fn test() -> Result<(), Error> {
+    let valves = crate::day16::load_valves(EXAMPLE)?;
     assert_eq!(max_pressure(valves, 26)?, Some(1707));
 
     let valves = crate::day16::load_valves(&std::fs::read_to_string("input/day16")?)?;
@@ -262,3 +139,68 @@ fn test() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_agents() -> Result<(), Error> {
+    let valves = crate::day16::load_valves(EXAMPLE)?;
+
+    // a single agent is part 1
+    let team = max_pressure_with_agents(&valves, "AA", 1, 30)?;
+    assert_eq!(team.pressure, 1651);
+    assert_eq!(team.agents, vec![crate::day16::max_pressure_from(&valves, "AA", 30)?]);
+
+    let opened = |plan: &Plan| -> Vec<String> {
+        let mut opened: Vec<String> = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                crate::day16::Step::Open { valve, .. } => Some(valve.clone()),
+                crate::day16::Step::Move { .. } => None,
+            })
+            .collect();
+        opened.sort();
+        opened
+    };
+
+    let team = max_pressure_with_agents(&valves, "AA", 2, 26)?;
+    assert_eq!(team, max_pressure_with_agents(&valves, "AA", 2, 26)?);
+    assert_eq!(team.agents.len(), 2);
+    assert_eq!(team.agents.iter().map(|plan| plan.pressure).sum::<i64>(), 1707);
+    let mut split: Vec<Vec<String>> = team.agents.iter().map(opened).collect();
+    split.sort();
+    assert_eq!(split, vec![vec!["BB", "CC", "JJ"], vec!["DD", "EE", "HH"]]);
+
+    // more agents never do worse, but there are only so many valves
+    let three = max_pressure_with_agents(&valves, "AA", 3, 26)?;
+    assert!(three.pressure >= 1707);
+    assert_eq!(three.agents.iter().map(|plan| plan.pressure).sum::<i64>(), three.pressure);
+    let mut all: Vec<String> = three.agents.iter().flat_map(opened).collect();
+    let num_opened = all.len();
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), num_opened);
+    let everyone = max_pressure_with_agents(&valves, "AA", 6, 26)?;
+    assert_eq!(everyone.pressure, max_pressure_with_agents(&valves, "AA", 7, 26)?.pressure);
+    assert_eq!(max_pressure_with_agents(&valves, "AA", 0, 26)?.pressure, 0);
+    assert_eq!(max_pressure_with_agents(&valves, "AA", 2, 1)?.pressure, 0);
+
+    // one flowing valve too many to split
+    let mut input = String::new();
+    let names: Vec<String> = (0..=MAX_SPLIT_VALVES)
+        .map(|index| format!("B{}", (b'A' + index as u8) as char))
+        .collect();
+    input.push_str(&format!("Valve AA has flow rate=0; tunnels lead to valves {}\n", names.join(", ")));
+    for name in names.iter() {
+        input.push_str(&format!("Valve {} has flow rate=1; tunnel leads to valve AA\n", name));
+    }
+    let valves = crate::day16::load_valves(&input)?;
+    let graph = ValveGraph::new(&valves, "AA")?;
+    assert_eq!(graph.num_flowing, MAX_SPLIT_VALVES + 1);
+    assert!(best_per_subset(&graph, 26).is_err());
+    assert!(max_pressure_with_agents(&valves, "AA", 2, 26).is_err());
+    let alone = max_pressure_with_agents(&valves, "AA", 1, 26)?;
+    assert_eq!(alone.agents, vec![crate::day16::max_pressure_from(&valves, "AA", 26)?]);
+    assert_eq!(alone.pressure, alone.agents[0].pressure);
+
+    Ok(())
+}
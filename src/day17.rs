@@ -3,6 +3,7 @@
 // part2: How tall will the tower be after 1000000000000 rocks have stopped?
 
 use crate::error::Error;
+use std::collections::HashMap;
 
 type Point = (i64, i64);
 
@@ -93,14 +94,14 @@ pub struct Patterns {
 }
 
 fn parse_patterns(s: &str) -> Result<Vec<Pattern>, Error> {
-    Ok(s.trim()
+    s.trim()
         .chars()
         .map(|c| match c {
-            '<' => Pattern::PushLeft,
-            '>' => Pattern::PushRight,
-            _ => panic!("invalid pattern: {}", c),
+            '<' => Ok(Pattern::PushLeft),
+            '>' => Ok(Pattern::PushRight),
+            _ => Err(Error::Parse(format!("invalid pattern: {}", c))),
         })
-        .collect())
+        .collect()
 }
 
 impl Patterns {
@@ -322,6 +323,76 @@ pub fn simulate(tetris: &mut Tetris, steps: usize) {
     }
 }
 
+// how far below the top the surface profile looks, deeper holes all count as this deep
+pub const SURFACE_ROWS: usize = 32;
+
+// for each column, how many rows below the top of the tower its highest settled rock is
+pub fn surface_profile(tetris: &Tetris) -> [usize; 7] {
+    let top = tetris.map.len() - tetris.settled_height();
+    let mut profile = [SURFACE_ROWS; 7];
+    for (x, depth) in profile.iter_mut().enumerate() {
+        if let Some(found) = (top..tetris.map.len().min(top + SURFACE_ROWS)).position(|y| tetris.map[y][x] == MapShape::SettledRock) {
+            *depth = found;
+        }
+    }
+    profile
+}
+
+// after `start` rocks the tower starts repeating itself, growing by the same amount every `length` rocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+pub struct TowerHeights {
+    // the height after every number of rocks, up to the end of the first cycle
+    pub heights: Vec<usize>,
+    pub cycle: Cycle,
+}
+
+impl TowerHeights {
+    // drops rocks until the next shape, the next jet and the surface of the tower are the same as after an earlier rock
+    pub fn new(jets: &str) -> Result<Self, Error> {
+        let patterns = parse_patterns(jets)?;
+        if patterns.is_empty() {
+            return Err(Error::Parse("no jet patterns".to_string()));
+        }
+        let mut tetris = Tetris::new(Patterns { patterns, next_pattern: 0 }, Shapes::new());
+        let mut heights = vec![0];
+        let mut seen: HashMap<(usize, usize, [usize; 7]), usize> = HashMap::new();
+        loop {
+            simulate(&mut tetris, 1);
+            heights.push(tetris.settled_height());
+            let state = (tetris.shapes.next_shape, tetris.patterns.next_pattern, surface_profile(&tetris));
+            let rocks = heights.len() - 1;
+            if let Some(&start) = seen.get(&state) {
+                return Ok(TowerHeights {
+                    heights,
+                    cycle: Cycle {
+                        start,
+                        length: rocks - start,
+                    },
+                });
+            }
+            seen.insert(state, rocks);
+        }
+    }
+
+    pub fn height_gain_per_cycle(&self) -> usize {
+        self.heights[self.cycle.start + self.cycle.length] - self.heights[self.cycle.start]
+    }
+
+    pub fn height_after(&self, rocks: u64) -> u64 {
+        if let Some(&height) = self.heights.get(rocks as usize) {
+            return height as u64;
+        }
+        let cycles = (rocks - self.cycle.start as u64) / self.cycle.length as u64;
+        let rest = (rocks - self.cycle.start as u64) % self.cycle.length as u64;
+        self.heights[self.cycle.start + rest as usize] as u64 + cycles * self.height_gain_per_cycle() as u64
+    }
+}
+
 #[test]
 fn test() -> Result<(), Error> {
     let input = r#">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"#;
@@ -399,3 +470,40 @@ fn test_shapes() -> Result<(), Error> {
     assert_eq!(shapelist.next_shape().height(), 2);
     Ok(())
 }
+
+#[test]
+fn test_cycle() -> Result<(), Error> {
+    let input = r#">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"#;
+    let heights = TowerHeights::new(input)?;
+    assert_eq!(heights.cycle.length % 5, 0);
+    assert_eq!(heights.height_after(0), 0);
+    assert_eq!(heights.height_after(3), 6);
+    assert_eq!(heights.height_after(2022), 3068);
+    assert_eq!(heights.height_after(1_000_000_000_000), 1514285714288);
+
+    // the extrapolation agrees with dropping every rock
+    let rocks = heights.cycle.start + 3 * heights.cycle.length + 7;
+    let mut tetris = Tetris::new(Patterns::new(input), Shapes::new());
+    simulate(&mut tetris, rocks);
+    assert_eq!(heights.height_after(rocks as u64), tetris.settled_height() as u64);
+
+    let mut tetris = Tetris::new(Patterns::new(input), Shapes::new());
+    simulate(&mut tetris, 1);
+    assert_eq!(surface_profile(&tetris), [SURFACE_ROWS, SURFACE_ROWS, 0, 0, 0, 0, SURFACE_ROWS]);
+
+    let input = std::fs::read_to_string("input/day17")?;
+    let heights = TowerHeights::new(&input)?;
+    assert_eq!(heights.cycle, Cycle { start: 197, length: 1745 });
+    assert_eq!(heights.height_after(2022), 3193);
+    let rocks = heights.cycle.start + 2 * heights.cycle.length + 11;
+    let mut tetris = Tetris::new(Patterns::new(&input), Shapes::new());
+    simulate(&mut tetris, rocks);
+    assert_eq!(heights.height_after(rocks as u64), tetris.settled_height() as u64);
+    assert_eq!(heights.height_after(1_000_000_000_000), 1577650429835);
+
+    assert!(TowerHeights::new("").is_err());
+    assert!(TowerHeights::new("\n").is_err());
+    assert!(TowerHeights::new("<>x").is_err());
+
+    Ok(())
+}
@@ -4,6 +4,7 @@
 
 use crate::error::Error;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Procedure {
     pub num: usize,
     pub from: usize,
@@ -37,6 +38,11 @@ pub fn load(input: &str) -> Result<(Vec<Vec<char>>, Vec<Procedure>), Error> {
             let to;
             try_scan!(line.bytes() => "move {} from {} to {}", num, from, to);
             procedures.push(Procedure { num, from, to });
+        } else if !line.trim().is_empty() && line.split_whitespace().all(|label| label.parse::<usize>().is_ok()) {
+            let num_stacks = line.split_whitespace().count();
+            while stacks.len() < num_stacks {
+                stacks.push(Vec::new());
+            }
         }
     }
     for s in stacks.iter_mut() {
@@ -45,25 +51,152 @@ pub fn load(input: &str) -> Result<(Vec<Vec<char>>, Vec<Procedure>), Error> {
     Ok((stacks, procedures))
 }
 
-pub fn apply_procedures_part1(mut input: Vec<Vec<char>>, procedures: Vec<Procedure>) -> Vec<Vec<char>> {
-    for procedure in procedures {
-        for _ in 0..procedure.num {
-            let popped = input[procedure.from - 1].pop().unwrap();
-            input[procedure.to - 1].push(popped);
+pub trait Crane {
+    fn lift(&mut self, stacks: &mut [Vec<char>], procedure: &Procedure) -> Result<(), Error>;
+}
+
+// part1: moves one crate at a time
+pub struct CrateMover9000;
+
+// part2: moves all crates at once, keeping their order
+pub struct CrateMover9001;
+
+// moves at most `capacity` crates at a time, keeping their order within each lift
+pub struct CapacityLimitedCrane {
+    pub capacity: usize,
+}
+
+// moves all crates at once, but turns them upside down on every other lift
+#[derive(Default)]
+pub struct AlternatingCrane {
+    lifts: usize,
+}
+
+impl Crane for CrateMover9000 {
+    fn lift(&mut self, stacks: &mut [Vec<char>], procedure: &Procedure) -> Result<(), Error> {
+        move_crates(stacks, procedure, true)
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn lift(&mut self, stacks: &mut [Vec<char>], procedure: &Procedure) -> Result<(), Error> {
+        move_crates(stacks, procedure, false)
+    }
+}
+
+impl Crane for CapacityLimitedCrane {
+    fn lift(&mut self, stacks: &mut [Vec<char>], procedure: &Procedure) -> Result<(), Error> {
+        if self.capacity == 0 {
+            return Err(Error::General("crane capacity must be at least 1".to_string()));
+        }
+        validate(stacks, procedure)?;
+        let mut remaining = procedure.num;
+        while remaining > 0 {
+            let num = remaining.min(self.capacity);
+            move_crates(stacks, &Procedure { num, ..*procedure }, false)?;
+            remaining -= num;
+        }
+        Ok(())
+    }
+}
+
+impl Crane for AlternatingCrane {
+    fn lift(&mut self, stacks: &mut [Vec<char>], procedure: &Procedure) -> Result<(), Error> {
+        move_crates(stacks, procedure, self.lifts % 2 == 1)?;
+        self.lifts += 1;
+        Ok(())
+    }
+}
+
+pub fn validate(stacks: &[Vec<char>], procedure: &Procedure) -> Result<(), Error> {
+    for stack in [procedure.from, procedure.to] {
+        if stack == 0 || stack > stacks.len() {
+            return Err(Error::General(format!(
+                "stack {} does not exist (have {} stacks)",
+                stack,
+                stacks.len()
+            )));
         }
     }
+    let available = stacks[procedure.from - 1].len();
+    if available < procedure.num {
+        return Err(Error::General(format!(
+            "cannot move {} crates from stack {} holding {}",
+            procedure.num, procedure.from, available
+        )));
+    }
+    Ok(())
+}
 
-    input
+fn move_crates(stacks: &mut [Vec<char>], procedure: &Procedure, reversed: bool) -> Result<(), Error> {
+    validate(stacks, procedure)?;
+    let new_len = stacks[procedure.from - 1].len() - procedure.num;
+    let mut moved = stacks[procedure.from - 1].split_off(new_len);
+    if reversed {
+        moved.reverse();
+    }
+    stacks[procedure.to - 1].append(&mut moved);
+    Ok(())
 }
 
-pub fn apply_procedures_part2(mut input: Vec<Vec<char>>, procedures: Vec<Procedure>) -> Vec<Vec<char>> {
+pub fn apply_procedures<C: Crane>(crane: &mut C, stacks: &[Vec<char>], procedures: &[Procedure]) -> Result<Vec<Vec<char>>, Error> {
+    let mut stacks = stacks.to_vec();
     for procedure in procedures {
-        let new_len = input[procedure.from - 1].len() - procedure.num;
-        let mut moved = input[procedure.from - 1].split_off(new_len);
-        input[procedure.to - 1].append(&mut moved);
+        crane.lift(&mut stacks, procedure)?;
+    }
+    Ok(stacks)
+}
+
+pub struct Simulation<'a, C: Crane> {
+    crane: C,
+    stacks: Vec<Vec<char>>,
+    procedures: std::slice::Iter<'a, Procedure>,
+}
+
+// yields the stacks after every procedure, and stops after the first one that fails
+pub fn simulate<'a, C: Crane>(crane: C, stacks: &[Vec<char>], procedures: &'a [Procedure]) -> Simulation<'a, C> {
+    Simulation {
+        crane,
+        stacks: stacks.to_vec(),
+        procedures: procedures.iter(),
+    }
+}
+
+impl<'a, C: Crane> Iterator for Simulation<'a, C> {
+    type Item = Result<Vec<Vec<char>>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let procedure = self.procedures.next()?;
+        if let Err(e) = self.crane.lift(&mut self.stacks, procedure) {
+            self.procedures = [].iter();
+            return Some(Err(e));
+        }
+        Some(Ok(self.stacks.clone()))
+    }
+}
+
+pub fn render(stacks: &[Vec<char>]) -> String {
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut lines = Vec::with_capacity(height + 1);
+    for level in (0..height).rev() {
+        let cells: Vec<String> = stacks
+            .iter()
+            .map(|stack| match stack.get(level) {
+                Some(crate_name) => format!("[{}]", crate_name),
+                None => "   ".to_string(),
+            })
+            .collect();
+        lines.push(cells.join(" "));
     }
+    let labels: Vec<String> = (1..=stacks.len()).map(|index| format!("{:^3}", index)).collect();
+    lines.push(labels.join(" "));
+    lines.join("\n") + "\n"
+}
 
-    input
+impl std::fmt::Display for Procedure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.num, self.from, self.to)
+    }
 }
 
 pub fn message(input: &[Vec<char>]) -> String {
@@ -100,20 +233,91 @@ move 1 from 1 to 2
     assert_eq!(stacks[0], vec!['Z', 'N']);
     assert_eq!(stacks[1], vec!['M', 'C', 'D']);
     assert_eq!(stacks[2], vec!['P']);
-    let transformed = apply_procedures_part1(stacks, procedure);
+    let transformed = apply_procedures(&mut CrateMover9000, &stacks, &procedure)?;
     assert_eq!(message(&transformed), "CMZ");
 
-    let (stacks, procedure) = load(input)?;
-    let transformed = apply_procedures_part2(stacks, procedure);
+    let transformed = apply_procedures(&mut CrateMover9001, &stacks, &procedure)?;
     assert_eq!(message(&transformed), "MCD");
 
     let (stacks, procedure) = load(&std::fs::read_to_string("input/day5")?)?;
-    let transformed = apply_procedures_part1(stacks, procedure);
+    let transformed = apply_procedures(&mut CrateMover9000, &stacks, &procedure)?;
     assert_eq!(message(&transformed), "LBLVVTVLP");
 
-    let (stacks, procedure) = load(&std::fs::read_to_string("input/day5")?)?;
-    let transformed = apply_procedures_part2(stacks, procedure);
+    let transformed = apply_procedures(&mut CrateMover9001, &stacks, &procedure)?;
     assert_eq!(message(&transformed), "TPFFBDRJD");
 
     Ok(())
 }
+
+#[test]
+fn test_cranes() -> Result<(), Error> {
+    let input = r#"
+    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+"#;
+
+    let (stacks, procedure) = load(input)?;
+    let transformed = apply_procedures(&mut CapacityLimitedCrane { capacity: 1 }, &stacks, &procedure)?;
+    assert_eq!(message(&transformed), "CMZ");
+    let transformed = apply_procedures(&mut CapacityLimitedCrane { capacity: 2 }, &stacks, &procedure)?;
+    assert_eq!(transformed, vec![vec!['M'], vec!['C'], vec!['P', 'N', 'D', 'Z']]);
+    let transformed = apply_procedures(&mut AlternatingCrane::default(), &stacks, &procedure)?;
+    assert_eq!(transformed, vec![vec!['M'], vec!['C'], vec!['P', 'D', 'N', 'Z']]);
+
+    let states = simulate(CrateMover9000, &stacks, &procedure).collect::<Result<Vec<_>, Error>>()?;
+    assert_eq!(states.len(), 4);
+    assert_eq!(states[0], vec![vec!['Z', 'N', 'D'], vec!['M', 'C'], vec!['P']]);
+    assert_eq!(states[1], vec![vec![], vec!['M', 'C'], vec!['P', 'D', 'N', 'Z']]);
+    assert_eq!(message(&states[3]), "CMZ");
+
+    let bad = Procedure { num: 4, from: 3, to: 1 };
+    assert!(apply_procedures(&mut CrateMover9001, &stacks, &[bad]).is_err());
+    assert!(apply_procedures(&mut CrateMover9000, &stacks, &[Procedure { num: 1, from: 1, to: 4 }]).is_err());
+    assert!(apply_procedures(&mut CapacityLimitedCrane { capacity: 0 }, &stacks, &procedure).is_err());
+    let procedures = [procedure[0], bad, procedure[1]];
+    let mut states = simulate(CrateMover9000, &stacks, &procedures);
+    assert!(states.next().unwrap().is_ok());
+    assert!(states.next().unwrap().is_err());
+    assert!(states.next().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_render() -> Result<(), Error> {
+    let input = r#"
+    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+"#;
+
+    let (stacks, procedure) = load(input)?;
+    assert_eq!(render(&stacks), input[1..input.find("\n\n").unwrap() + 1]);
+    assert_eq!(procedure[1].to_string(), "move 3 from 1 to 3");
+    for state in simulate(CrateMover9000, &stacks, &procedure) {
+        let state = state?;
+        assert_eq!(load(&render(&state))?.0, state);
+    }
+    assert_eq!(render(&[vec![], vec!['A'], vec![]]), "    [A]    \n 1   2   3 \n");
+    assert_eq!(load(&render(&[vec![], vec![]]))?.0, vec![vec![], vec![]]);
+
+    let input = std::fs::read_to_string("input/day5")?;
+    let (stacks, procedure) = load(&input)?;
+    assert_eq!(render(&stacks), input[..input.find("\n\n").unwrap() + 1]);
+    assert_eq!(procedure[0].to_string(), input.lines().nth(10).unwrap());
+
+    Ok(())
+}
@@ -3,43 +3,149 @@
 // part2: find marker of len 14 in a string
 
 use crate::error::Error;
-use std::collections::HashSet;
-use std::hash::Hash;
-
-fn hashset_from_slice<R>(slice: &[R]) -> HashSet<R>
-where
-    R: Eq + Hash + Copy,
-{
-    let mut set = HashSet::with_capacity(slice.len());
-    for c in slice {
-        set.insert(*c);
+use std::io::{BufReader, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub len: usize,
+    // number of characters processed when the marker is complete
+    pub position: usize,
+}
+
+struct Window {
+    len: usize,
+    counts: [usize; 256],
+    distinct: usize,
+}
+
+// keeps a count table per marker length, so every byte costs O(number of marker lengths)
+pub struct MarkerScanner {
+    history: Vec<u8>,
+    position: usize,
+    windows: Vec<Window>,
+}
+
+impl MarkerScanner {
+    pub fn new(marker_lens: &[usize]) -> Result<Self, Error> {
+        if marker_lens.is_empty() || marker_lens.contains(&0) {
+            return Err(Error::General(format!("invalid marker lengths: {:?}", marker_lens)));
+        }
+        let windows = marker_lens
+            .iter()
+            .map(|&len| Window {
+                len,
+                counts: [0; 256],
+                distinct: 0,
+            })
+            .collect();
+        Ok(Self {
+            history: vec![0; *marker_lens.iter().max().unwrap()],
+            position: 0,
+            windows,
+        })
+    }
+
+    // returns the markers that end with this byte
+    pub fn push(&mut self, byte: u8) -> Vec<Marker> {
+        let mut markers = Vec::new();
+        let capacity = self.history.len();
+        for window in self.windows.iter_mut() {
+            if self.position >= window.len {
+                let old = self.history[(self.position - window.len) % capacity] as usize;
+                window.counts[old] -= 1;
+                if window.counts[old] == 0 {
+                    window.distinct -= 1;
+                }
+            }
+            if window.counts[byte as usize] == 0 {
+                window.distinct += 1;
+            }
+            window.counts[byte as usize] += 1;
+            if window.distinct == window.len {
+                markers.push(Marker {
+                    len: window.len,
+                    position: self.position + 1,
+                });
+            }
+        }
+        self.history[self.position % capacity] = byte;
+        self.position += 1;
+        markers
+    }
+}
+
+// line breaks are not part of the signal and are skipped
+fn signal_bytes<R: Read>(reader: R) -> impl Iterator<Item = Result<u8, Error>> {
+    BufReader::new(reader)
+        .bytes()
+        .filter(|b| !matches!(b, Ok(b'\n') | Ok(b'\r')))
+        .map(|b| b.map_err(Error::from))
+}
+
+pub fn find_all_markers<R: Read>(reader: R, marker_lens: &[usize]) -> Result<Vec<Marker>, Error> {
+    let mut scanner = MarkerScanner::new(marker_lens)?;
+    let mut markers = Vec::new();
+    for byte in signal_bytes(reader) {
+        markers.append(&mut scanner.push(byte?));
+    }
+    Ok(markers)
+}
+
+pub fn find_first_marker<R: Read>(reader: R, marker_len: usize) -> Result<usize, Error> {
+    let mut scanner = MarkerScanner::new(&[marker_len])?;
+    for byte in signal_bytes(reader) {
+        if let Some(marker) = scanner.push(byte?).first() {
+            return Ok(marker.position);
+        }
     }
-    set
+    Err(Error::General("no marker found".to_string()))
 }
 
 pub fn find_marker(input: &str, marker_len: usize) -> Result<(String, usize), Error> {
-    for (index, window) in input.as_bytes().windows(marker_len).enumerate() {
-        if hashset_from_slice(window).len() == marker_len {
-            return Ok((String::from_utf8_lossy(window).to_string(), index + marker_len));
+    let mut scanner = MarkerScanner::new(&[marker_len])?;
+    for &byte in input.as_bytes() {
+        if let Some(marker) = scanner.push(byte).first() {
+            let window = &input.as_bytes()[marker.position - marker_len..marker.position];
+            return Ok((String::from_utf8_lossy(window).to_string(), marker.position));
         }
     }
     Err(Error::General("no marker found".to_string()))
 }
 
+#[cfg(test)]
+const EXAMPLE: &str = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+
 #[test]
 fn test() -> Result<(), Error> {
-    assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4)?, ("jpqm".to_string(), 7));
+    assert_eq!(find_marker(EXAMPLE, 4)?, ("jpqm".to_string(), 7));
     assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 4)?, ("vwbj".to_string(), 5));
     assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 4)?.1, 6);
     let input = std::fs::read_to_string("input/day6")?;
     assert_eq!(find_marker(&input, 4)?, ("fwgm".to_string(), 1480));
 
-    assert_eq!(
-        find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14)?,
-        ("qmgbljsphdztnv".to_string(), 19)
-    );
-    let input = std::fs::read_to_string("input/day6")?;
+    assert_eq!(find_marker(EXAMPLE, 14)?, ("qmgbljsphdztnv".to_string(), 19));
     assert_eq!(find_marker(&input, 14)?, ("mwncpfhvqlsbtr".to_string(), 2746));
 
     Ok(())
 }
+
+#[test]
+fn test_stream() -> Result<(), Error> {
+    let stream = "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw".as_bytes();
+    assert_eq!(find_first_marker(stream, 4)?, 11);
+    assert_eq!(find_first_marker(stream, 14)?, 26);
+    assert_eq!(find_first_marker(EXAMPLE.as_bytes(), 14)?, 19);
+    assert!(find_first_marker("aaaa\n".as_bytes(), 2).is_err());
+    assert!(find_first_marker("abcd".as_bytes(), 0).is_err());
+
+    let markers = find_all_markers("abcabba\n".as_bytes(), &[2, 3])?;
+    let positions = |len| markers.iter().filter(|m| m.len == len).map(|m| m.position).collect::<Vec<_>>();
+    assert_eq!(positions(2), vec![2, 3, 4, 5, 7]);
+    assert_eq!(positions(3), vec![3, 4, 5]);
+
+    let markers = find_all_markers(std::fs::File::open("input/day6")?, &[4, 14])?;
+    assert_eq!(markers.iter().find(|m| m.len == 4).unwrap().position, 1480);
+    assert_eq!(markers.iter().find(|m| m.len == 14).unwrap().position, 2746);
+
+    Ok(())
+}
@@ -4,49 +4,231 @@
 
 use crate::error::Error;
 use std::collections::HashMap;
+use std::str::FromStr;
 
-pub fn get_path_from_directory_stack(directories: &[String]) -> String {
-    "/".to_string() + &directories.join("/")
+pub type DirectoryId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub name: String,
+    pub size: usize,
+}
+
+#[derive(Debug)]
+pub struct Directory {
+    pub name: String,
+    pub parent: Option<DirectoryId>,
+    pub directories: Vec<DirectoryId>,
+    pub files: Vec<File>,
+}
+
+// directories are stored in creation order, so a parent always comes before its children
+#[derive(Debug)]
+pub struct FileSystem {
+    pub directories: Vec<Directory>,
 }
 
-pub fn get_paths_from_directory_stack(stack: &[String]) -> Vec<String> {
-    let mut result = Vec::with_capacity(stack.len() + 1);
-    for index in 0..stack.len() {
-        result.push(get_path_from_directory_stack(&stack[0..stack.len() - index]));
+impl FromStr for FileSystem {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const COMMAND_CD_ROOT: &str = "$ cd /";
+        const COMMAND_CD_PARENT: &str = "$ cd ..";
+        const COMMAND_CD_DIRECTORY: &str = "$ cd ";
+        const COMMAND_LS: &str = "$ ls";
+        const OUTPUT_DIR: &str = "dir ";
+        let mut fs = FileSystem {
+            directories: vec![Directory {
+                name: "/".to_string(),
+                parent: None,
+                directories: Vec::new(),
+                files: Vec::new(),
+            }],
+        };
+        let mut current = fs.root();
+        let mut listing = false;
+        for (line_index, line) in s.trim_start().trim_end().lines().enumerate() {
+            let inconsistent = |reason: &str| Error::Parse(format!("line {}: {}: {}", line_index + 1, reason, line));
+            if line.is_empty() {
+                continue;
+            } else if line == COMMAND_LS {
+                listing = true;
+            } else if line == COMMAND_CD_ROOT {
+                listing = false;
+                current = fs.root();
+            } else if line == COMMAND_CD_PARENT {
+                listing = false;
+                current = fs.directories[current].parent.ok_or_else(|| inconsistent("cd .. at root"))?;
+            } else if line.starts_with(COMMAND_CD_DIRECTORY) {
+                listing = false;
+                let name = line.trim_start_matches(COMMAND_CD_DIRECTORY);
+                current = fs.add_directory(current, name).map_err(|e| inconsistent(&e))?;
+            } else if line.starts_with('$') {
+                return Err(inconsistent("unknown command"));
+            } else if !listing {
+                return Err(inconsistent("output without ls"));
+            } else if line.starts_with(OUTPUT_DIR) {
+                fs.add_directory(current, line.trim_start_matches(OUTPUT_DIR))
+                    .map_err(|e| inconsistent(&e))?;
+            } else {
+                use text_io::try_scan;
+                let size: usize;
+                let name: String;
+                try_scan!(line.bytes() => "{} {}", size, name);
+                fs.add_file(current, File { name, size }).map_err(|e| inconsistent(&e))?;
+            }
+        }
+        Ok(fs)
     }
-    result.push("/".to_string());
-    result
 }
 
-pub fn compute(input: &str) -> Result<HashMap<String, usize>, Error> {
-    const COMMAND_CD_ROOT: &str = "$ cd /";
-    const COMMAND_CD_PARENT: &str = "$ cd ..";
-    const COMMAND_CD_DIRECTORY: &str = "$ cd ";
-    const COMMAND_LS: &str = "$ ls";
-    const OUTPUT_DIR: &str = "dir ";
-    let mut result = HashMap::new();
-    let mut directory_stack = Vec::new();
-    for line in input.trim_start().trim_end().lines() {
-        if line.is_empty() || line.starts_with(COMMAND_LS) || line.starts_with(OUTPUT_DIR) {
-            continue;
-        } else if line.starts_with(COMMAND_CD_ROOT) {
-            directory_stack.clear();
-        } else if line.starts_with(COMMAND_CD_PARENT) {
-            directory_stack.truncate(directory_stack.len() - 1);
-        } else if line.starts_with(COMMAND_CD_DIRECTORY) {
-            let folder = line.trim_start_matches(COMMAND_CD_DIRECTORY);
-            directory_stack.push(folder.to_owned());
-        } else {
-            use text_io::try_scan;
-            let size: usize;
-            let filename: String;
-            try_scan!(line.bytes() => "{} {}", size, filename);
-            for directory in get_paths_from_directory_stack(&directory_stack) {
-                *result.entry(directory).or_insert(0) += size;
+impl FileSystem {
+    pub fn root(&self) -> DirectoryId {
+        0
+    }
+
+    // returns the existing directory if it has already been seen
+    fn add_directory(&mut self, parent: DirectoryId, name: &str) -> Result<DirectoryId, String> {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(format!("invalid directory name '{}'", name));
+        }
+        if let Some(id) = self.child(parent, name) {
+            return Ok(id);
+        }
+        if self.directories[parent].files.iter().any(|f| f.name == name) {
+            return Err(format!("'{}' is already a file", name));
+        }
+        let id = self.directories.len();
+        self.directories.push(Directory {
+            name: name.to_string(),
+            parent: Some(parent),
+            directories: Vec::new(),
+            files: Vec::new(),
+        });
+        self.directories[parent].directories.push(id);
+        Ok(id)
+    }
+
+    fn add_file(&mut self, parent: DirectoryId, file: File) -> Result<(), String> {
+        if self.child(parent, &file.name).is_some() {
+            return Err(format!("'{}' is already a directory", file.name));
+        }
+        match self.directories[parent].files.iter().find(|f| f.name == file.name) {
+            Some(existing) if existing.size != file.size => {
+                Err(format!("'{}' listed with size {} and {}", file.name, existing.size, file.size))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.directories[parent].files.push(file);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn child(&self, parent: DirectoryId, name: &str) -> Option<DirectoryId> {
+        self.directories[parent]
+            .directories
+            .iter()
+            .copied()
+            .find(|&id| self.directories[id].name == name)
+    }
+
+    pub fn find(&self, path: &str) -> Option<DirectoryId> {
+        let mut current = self.root();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            current = self.child(current, name)?;
+        }
+        Some(current)
+    }
+
+    pub fn find_file(&self, path: &str) -> Option<&File> {
+        let (directory, name) = path.rsplit_once('/')?;
+        self.directories[self.find(directory)?].files.iter().find(|f| f.name == name)
+    }
+
+    pub fn path(&self, id: DirectoryId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.directories[current].parent {
+            names.push(self.directories[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+        "/".to_string() + &names.join("/")
+    }
+
+    // total size of every directory, indexed by id
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self.directories.iter().map(|d| d.files.iter().map(|f| f.size).sum()).collect();
+        for id in (1..self.directories.len()).rev() {
+            let parent = self.directories[id].parent.unwrap();
+            sizes[parent] += sizes[id];
+        }
+        sizes
+    }
+
+    pub fn size(&self, id: DirectoryId) -> usize {
+        self.walk(id)
+            .iter()
+            .map(|&d| self.directories[d].files.iter().map(|f| f.size).sum::<usize>())
+            .sum()
+    }
+
+    // depth-first, parents before children, children sorted by name
+    pub fn walk(&self, id: DirectoryId) -> Vec<DirectoryId> {
+        let mut result = Vec::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            result.push(current);
+            let mut children = self.directories[current].directories.clone();
+            children.sort_by(|&a, &b| self.directories[b].name.cmp(&self.directories[a].name));
+            stack.extend(children);
+        }
+        result
+    }
+
+    // like `du`, children before their parent
+    pub fn du(&self) -> String {
+        let sizes = self.sizes();
+        let mut lines: Vec<String> = self
+            .walk(self.root())
+            .into_iter()
+            .map(|id| format!("{}\t{}", sizes[id], self.path(id)))
+            .collect();
+        lines.reverse();
+        lines.join("\n")
+    }
+
+    // same format as the puzzle description
+    pub fn tree(&self) -> String {
+        let mut lines = Vec::new();
+        self.tree_lines(self.root(), 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn tree_lines(&self, id: DirectoryId, depth: usize, lines: &mut Vec<String>) {
+        let directory = &self.directories[id];
+        lines.push(format!("{}- {} (dir)", "  ".repeat(depth), directory.name));
+        let mut entries: Vec<(&str, Option<&File>, DirectoryId)> = directory
+            .directories
+            .iter()
+            .map(|&child| (self.directories[child].name.as_str(), None, child))
+            .chain(directory.files.iter().map(|f| (f.name.as_str(), Some(f), id)))
+            .collect();
+        entries.sort_by_key(|entry| entry.0);
+        for (name, file, child) in entries {
+            match file {
+                Some(file) => lines.push(format!("{}- {} (file, size={})", "  ".repeat(depth + 1), name, file.size)),
+                None => self.tree_lines(child, depth + 1, lines),
             }
         }
     }
-    Ok(result)
+}
+
+pub fn compute(input: &str) -> Result<HashMap<String, usize>, Error> {
+    let fs: FileSystem = input.parse()?;
+    let sizes = fs.sizes();
+    Ok((0..fs.directories.len()).map(|id| (fs.path(id), sizes[id])).collect())
 }
 
 pub fn score(directories: &HashMap<String, usize>) -> usize {
@@ -59,23 +241,166 @@ pub fn score(directories: &HashMap<String, usize>) -> usize {
     total
 }
 
-pub fn smallest(directories: &HashMap<String, usize>) -> usize {
-    let mut candidates = Vec::new();
-    let currently_used = directories["/"];
-    let currently_unused = 70000000 - currently_used;
-    let need_more = 30000000 - currently_unused;
-    for v in directories.values() {
-        if v > &need_more {
-            candidates.push(*v);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupStrategy {
+    // a single directory, as small as possible
+    SmallestDirectory,
+    // any number of directories, freeing as little as possible in total
+    LeastSpaceFreed,
+    // as few directories as possible
+    FewestDeletions,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CleanupPlan {
+    pub directories: Vec<DirectoryId>,
+    pub freed: usize,
+}
+
+// the root directory cannot be deleted, every other directory can
+pub struct CleanupPlanner {
+    pub disk_size: usize,
+    pub required_space: usize,
+}
+
+impl Default for CleanupPlanner {
+    fn default() -> Self {
+        Self {
+            disk_size: 70_000_000,
+            required_space: 30_000_000,
         }
     }
-    candidates.sort();
-    *candidates.first().unwrap()
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+impl CleanupPlanner {
+    pub fn space_to_free(&self, fs: &FileSystem) -> Result<usize, Error> {
+        let used = fs.sizes()[fs.root()];
+        if used > self.disk_size {
+            return Err(Error::General(format!("{} used on a disk of size {}", used, self.disk_size)));
+        }
+        Ok(self.required_space.saturating_sub(self.disk_size - used))
+    }
+
+    pub fn plan(&self, fs: &FileSystem, strategy: CleanupStrategy) -> Result<CleanupPlan, Error> {
+        let needed = self.space_to_free(fs)?;
+        let sizes = fs.sizes();
+        if needed == 0 {
+            return Ok(CleanupPlan {
+                directories: Vec::new(),
+                freed: 0,
+            });
+        }
+        let plan = match strategy {
+            CleanupStrategy::SmallestDirectory => Self::smallest_directory(fs, &sizes, needed),
+            CleanupStrategy::LeastSpaceFreed => Self::least_space_freed(fs, &sizes, needed),
+            CleanupStrategy::FewestDeletions => Self::fewest_deletions(fs, &sizes, needed),
+        };
+        plan.ok_or_else(|| Error::General(format!("cannot free {} by deleting directories", needed)))
+    }
+
+    fn smallest_directory(fs: &FileSystem, sizes: &[usize], needed: usize) -> Option<CleanupPlan> {
+        (1..fs.directories.len())
+            .filter(|&id| sizes[id] >= needed)
+            .min_by_key(|&id| sizes[id])
+            .map(|id| CleanupPlan {
+                directories: vec![id],
+                freed: sizes[id],
+            })
+    }
+
+    // every chosen directory lies inside a distinct top level directory, which is at least as large,
+    // so taking the largest top level directories first needs the fewest deletions
+    fn fewest_deletions(fs: &FileSystem, sizes: &[usize], needed: usize) -> Option<CleanupPlan> {
+        if let Some(plan) = Self::smallest_directory(fs, sizes, needed) {
+            return Some(plan);
+        }
+        let mut top_level = fs.directories[fs.root()].directories.clone();
+        top_level.sort_by_key(|&id| std::cmp::Reverse(sizes[id]));
+        let mut plan = CleanupPlan {
+            directories: Vec::new(),
+            freed: 0,
+        };
+        for id in top_level {
+            plan.directories.push(id);
+            plan.freed += sizes[id];
+            if plan.freed >= needed {
+                return Some(plan);
+            }
+        }
+        None
+    }
+
+    // branch and bound over the directories in depth-first order, where choosing a directory skips its subtree.
+    // a directory is never worth less than its own subdirectories, so taking every directory that is not inside
+    // one already considered bounds what can still be freed. the fewest deletions plan bounds the answer
+    fn least_space_freed(fs: &FileSystem, sizes: &[usize], needed: usize) -> Option<CleanupPlan> {
+        let order: Vec<DirectoryId> = fs.walk(fs.root()).into_iter().skip(1).collect();
+        let subtree_end: Vec<usize> = order
+            .iter()
+            .enumerate()
+            .map(|(position, &id)| position + fs.walk(id).len())
+            .collect();
+        let mut most_from = vec![0; order.len() + 1];
+        for position in (0..order.len()).rev() {
+            most_from[position] = sizes[order[position]] + most_from[subtree_end[position]];
+        }
+        let mut search = LeastSpaceFreedSearch {
+            order: &order,
+            subtree_end: &subtree_end,
+            most_from: &most_from,
+            sizes,
+            needed,
+            chosen: Vec::new(),
+            best: Self::fewest_deletions(fs, sizes, needed)?,
+        };
+        search.visit(0, 0);
+        Some(search.best)
+    }
+}
+
+struct LeastSpaceFreedSearch<'a> {
+    order: &'a [DirectoryId],
+    subtree_end: &'a [usize],
+    // the most that can be freed using only directories from this position on
+    most_from: &'a [usize],
+    sizes: &'a [usize],
+    needed: usize,
+    chosen: Vec<DirectoryId>,
+    best: CleanupPlan,
+}
+
+impl LeastSpaceFreedSearch<'_> {
+    fn visit(&mut self, position: usize, freed: usize) {
+        if freed >= self.best.freed || self.best.freed == self.needed {
+            return;
+        }
+        if freed >= self.needed {
+            self.best = CleanupPlan {
+                directories: self.chosen.clone(),
+                freed,
+            };
+            return;
+        }
+        if position == self.order.len() || freed + self.most_from[position] < self.needed {
+            return;
+        }
+        let id = self.order[position];
+        self.chosen.push(id);
+        self.visit(self.subtree_end[position], freed + self.sizes[id]);
+        self.chosen.pop();
+        self.visit(position + 1, freed);
+    }
+}
+
+// part2 on the sizes from `compute`, on the default disk
+pub fn smallest(directories: &HashMap<String, usize>) -> usize {
+    let planner = CleanupPlanner::default();
+    let needed = planner.required_space.saturating_sub(planner.disk_size - directories["/"]);
+    *directories.values().filter(|&&size| size >= needed).min().unwrap()
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 $ cd /
 $ ls
 dir a
@@ -100,17 +425,155 @@ $ ls
 5626152 d.ext
 7214296 k"#;
 
-    let directories = compute(input)?;
+#[test]
+fn test() -> Result<(), Error> {
+    let directories = compute(EXAMPLE)?;
     assert_eq!(directories["/d"], 24933642);
     assert_eq!(directories["/a/e"], 584);
     assert_eq!(directories["/"], 48381165);
     assert_eq!(score(&directories), 95437);
     assert_eq!(smallest(&directories), 24933642);
+    let fs: FileSystem = EXAMPLE.parse()?;
+    let plan = CleanupPlanner::default().plan(&fs, CleanupStrategy::SmallestDirectory)?;
+    assert_eq!(plan.freed, 24933642);
 
     let input = std::fs::read_to_string("input/day7")?;
     let directories = compute(&input)?;
     assert_eq!(score(&directories), 1778099);
     assert_eq!(smallest(&directories), 1623571);
+    let fs: FileSystem = input.parse()?;
+    let plan = CleanupPlanner::default().plan(&fs, CleanupStrategy::SmallestDirectory)?;
+    assert_eq!(plan.freed, 1623571);
+
+    Ok(())
+}
+
+#[test]
+fn test_file_system() -> Result<(), Error> {
+    let fs: FileSystem = EXAMPLE.parse()?;
+    assert_eq!(
+        fs.tree(),
+        r#"- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)"#
+    );
+    assert_eq!(fs.du(), "24933642\t/d\n584\t/a/e\n94853\t/a\n48381165\t/");
+    let e = fs.find("/a/e").unwrap();
+    assert_eq!(fs.path(e), "/a/e");
+    assert_eq!(fs.size(e), 584);
+    assert_eq!(fs.size(fs.root()), 48381165);
+    assert_eq!(fs.directories[e].parent, fs.find("/a"));
+    assert_eq!(fs.find("/a/x"), None);
+    assert_eq!(fs.find_file("/d/k").map(|f| f.size), Some(7214296));
+    assert_eq!(fs.find_file("/b.txt").map(|f| f.size), Some(14848514));
+
+    assert!("$ cd /\n$ cd ..".parse::<FileSystem>().is_err());
+    assert!("$ cd /\n$ ls\n1 a\n$ cd a".parse::<FileSystem>().is_err());
+    assert!("$ cd /\n$ ls\n1 a\n2 a".parse::<FileSystem>().is_err());
+    assert!("$ cd /\n1 a".parse::<FileSystem>().is_err());
+    assert!("$ cd /\n$ rm -rf a".parse::<FileSystem>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_cleanup() -> Result<(), Error> {
+    let input = r#"
+$ cd /
+$ ls
+dir a
+dir b
+dir c
+10 x
+$ cd a
+$ ls
+dir d
+40 y
+$ cd d
+$ ls
+30 z
+$ cd /
+$ cd b
+$ ls
+35 y
+$ cd ..
+$ cd c
+$ ls
+25 y"#;
+
+    let fs: FileSystem = input.parse()?;
+    let id = |path| fs.find(path).unwrap();
+    let planner = CleanupPlanner {
+        disk_size: 200,
+        required_space: 150,
+    };
+    assert_eq!(planner.space_to_free(&fs)?, 90);
+    assert!(planner.plan(&fs, CleanupStrategy::SmallestDirectory).is_err());
+    assert_eq!(
+        planner.plan(&fs, CleanupStrategy::FewestDeletions)?,
+        CleanupPlan {
+            directories: vec![id("/a"), id("/b")],
+            freed: 105
+        }
+    );
+    assert_eq!(
+        planner.plan(&fs, CleanupStrategy::LeastSpaceFreed)?,
+        CleanupPlan {
+            directories: vec![id("/a/d"), id("/b"), id("/c")],
+            freed: 90
+        }
+    );
+
+    let planner = CleanupPlanner {
+        disk_size: 200,
+        required_space: 120,
+    };
+    assert_eq!(planner.plan(&fs, CleanupStrategy::SmallestDirectory)?.directories, vec![id("/a")]);
+    assert_eq!(planner.plan(&fs, CleanupStrategy::FewestDeletions)?.directories, vec![id("/a")]);
+    assert_eq!(planner.plan(&fs, CleanupStrategy::LeastSpaceFreed)?.freed, 60);
+    assert_eq!(
+        planner.plan(&fs, CleanupStrategy::LeastSpaceFreed)?.directories,
+        vec![id("/b"), id("/c")]
+    );
+
+    let planner = CleanupPlanner {
+        disk_size: 200,
+        required_space: 50,
+    };
+    assert_eq!(
+        planner.plan(&fs, CleanupStrategy::LeastSpaceFreed)?.directories,
+        Vec::<DirectoryId>::new()
+    );
+    assert!(CleanupPlanner {
+        disk_size: 100,
+        required_space: 0
+    }
+    .space_to_free(&fs)
+    .is_err());
+    assert!(CleanupPlanner {
+        disk_size: 200,
+        required_space: 200
+    }
+    .plan(&fs, CleanupStrategy::LeastSpaceFreed)
+    .is_err());
+
+    let fs: FileSystem = std::fs::read_to_string("input/day7")?.parse()?;
+    let planner = CleanupPlanner::default();
+    let plan = planner.plan(&fs, CleanupStrategy::LeastSpaceFreed)?;
+    let needed = planner.space_to_free(&fs)?;
+    assert!(plan.freed >= needed && plan.freed <= 1623571);
+    assert_eq!(plan.freed, plan.directories.iter().map(|&id| fs.sizes()[id]).sum::<usize>());
 
     Ok(())
 }
@@ -3,6 +3,7 @@
 // part2: Consider each tree on your map. What is the highest scenic score possible for any tree?
 
 use crate::error::Error;
+use crate::utils::render_grid;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -98,45 +99,188 @@ impl Map {
     }
 
     pub fn count_visible(&self) -> usize {
-        let mut count = 0;
-        for y in 0..self.height() {
-            for x in 0..self.width() {
-                if self.is_visible(x, y) {
-                    count += 1;
+        self.visibility().count_visible()
+    }
+
+    pub fn highest_scenic_score(&self) -> (usize, usize, usize) {
+        self.visibility().highest_scenic_score()
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        let (width, height) = (self.width(), self.height());
+        let heights: Vec<usize> = self.map.iter().flatten().copied().collect();
+        let mut visibility = Visibility {
+            width,
+            height,
+            visible: std::array::from_fn(|_| vec![false; width * height]),
+            distance: std::array::from_fn(|_| vec![0; width * height]),
+        };
+        for direction in DIRECTIONS {
+            let lines: Vec<Vec<usize>> = match direction {
+                Direction::Left => (0..height).map(|y| (0..width).map(|x| y * width + x).collect()).collect(),
+                Direction::Right => (0..height).map(|y| (0..width).rev().map(|x| y * width + x).collect()).collect(),
+                Direction::Up => (0..width).map(|x| (0..height).map(|y| y * width + x).collect()).collect(),
+                Direction::Down => (0..width).map(|x| (0..height).rev().map(|y| y * width + x).collect()).collect(),
+            };
+            for line in lines {
+                visibility.sweep(direction, &heights, &line);
+            }
+        }
+        visibility
+    }
+
+    // trees seen when looking from `observer` along each direction: every tree up to and including the
+    // first one that is at least as tall as the observer's eyes
+    pub fn visible_from(&self, observer: &Observer, diagonal: bool) -> Vec<(usize, usize)> {
+        let directions: &[(i64, i64)] = if diagonal {
+            &LINE_OF_SIGHT_DIRECTIONS
+        } else {
+            &LINE_OF_SIGHT_DIRECTIONS[..4]
+        };
+        let mut visible = Vec::new();
+        for &(dx, dy) in directions {
+            let (mut x, mut y) = (observer.x as i64 + dx, observer.y as i64 + dy);
+            while x >= 0 && y >= 0 && (x as usize) < self.width() && (y as usize) < self.height() {
+                visible.push((x as usize, y as usize));
+                if self.tree_height_at(x as usize, y as usize) >= observer.height {
+                    break;
                 }
+                x += dx;
+                y += dy;
             }
         }
-        count
+        visible.sort();
+        visible
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observer {
+    pub x: usize,
+    pub y: usize,
+    pub height: usize,
+}
+
+// straight directions first, then diagonals
+pub const LINE_OF_SIGHT_DIRECTIONS: [(i64, i64); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+pub const DIRECTIONS: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+// visibility and viewing distance of every tree in every direction, computed in O(width * height)
+pub struct Visibility {
+    width: usize,
+    height: usize,
+    visible: [Vec<bool>; 4],
+    distance: [Vec<usize>; 4],
+}
+
+impl Visibility {
+    // `line` lists the cells in the order they are seen when looking from the edge towards `direction`.
+    // The stack holds the trees that can still block a later tree, tallest at the bottom.
+    fn sweep(&mut self, direction: Direction, heights: &[usize], line: &[usize]) {
+        let mut stack: Vec<usize> = Vec::with_capacity(line.len());
+        for (index, &cell) in line.iter().enumerate() {
+            while let Some(&top) = stack.last() {
+                if heights[line[top]] >= heights[cell] {
+                    break;
+                }
+                stack.pop();
+            }
+            let (visible, distance) = match stack.last() {
+                Some(&blocker) => (false, index - blocker),
+                None => (true, index),
+            };
+            self.visible[direction as usize][cell] = visible;
+            self.distance[direction as usize][cell] = distance;
+            stack.push(index);
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // same as `Map::visibility_left` and friends
+    pub fn visibility_towards(&self, direction: Direction, x: usize, y: usize) -> (bool, usize) {
+        let cell = y * self.width + x;
+        (self.visible[direction as usize][cell], self.distance[direction as usize][cell])
+    }
+
+    pub fn is_visible(&self, x: usize, y: usize) -> bool {
+        DIRECTIONS.iter().any(|&direction| self.visibility_towards(direction, x, y).0)
+    }
+
+    pub fn scenic_score(&self, x: usize, y: usize) -> usize {
+        DIRECTIONS
+            .iter()
+            .map(|&direction| self.visibility_towards(direction, x, y).1)
+            .product()
+    }
+
+    pub fn count_visible(&self) -> usize {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.is_visible(x, y))
+            .count()
+    }
+
+    // the `n` best scenic scores as (score, x, y), ties in row order
+    pub fn top_scenic_scores(&self, n: usize) -> Vec<(usize, usize, usize)> {
+        let mut scores: Vec<(usize, usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| (self.scenic_score(x, y), x, y))
+            .collect();
+        scores.sort_by_key(|score| std::cmp::Reverse(score.0));
+        scores.truncate(n);
+        scores
+    }
+
+    // darker characters for higher scenic scores, relative to the best score on the map
+    pub fn scenic_heatmap(&self) -> String {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let highest = self.highest_scenic_score().0.max(1);
+        render_grid(self.width, self.height, |x, y| {
+            SHADES[self.scenic_score(x, y) * (SHADES.len() - 1) / highest] as char
+        })
     }
 
     pub fn highest_scenic_score(&self) -> (usize, usize, usize) {
-        let mut highest_score = 0;
-        let mut highest_x = 0;
-        let mut highest_y = 0;
-        for x in 0..self.width() {
-            for y in 0..self.height() {
+        let mut highest = (0, 0, 0);
+        for x in 0..self.width {
+            for y in 0..self.height {
                 let score = self.scenic_score(x, y);
-                if score > highest_score {
-                    highest_score = score;
-                    highest_x = x;
-                    highest_y = y;
+                if score > highest.0 {
+                    highest = (score, x, y);
                 }
             }
         }
-        (highest_score, highest_x, highest_y)
+        highest
     }
 }
 
-#[test]
-fn test() -> Result<(), Error> {
-    let input = r#"
+#[cfg(test)]
+const EXAMPLE: &str = r#"
 30373
 25512
 65332
 33549
 35390"#;
 
-    let map: Map = input.parse()?;
+#[test]
+fn test() -> Result<(), Error> {
+    let map: Map = EXAMPLE.parse()?;
     assert_eq!(map.width(), 5);
     assert_eq!(map.height(), 5);
     assert_eq!(map.count_visible(), 21);
@@ -171,3 +315,68 @@ fn test() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_visibility() -> Result<(), Error> {
+    let mut seed = 12345usize;
+    let mut random_height = || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % 10
+    };
+    let map = Map {
+        map: (0..40).map(|_| (0..70).map(|_| random_height()).collect()).collect(),
+    };
+    let visibility = map.visibility();
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            assert_eq!(visibility.visibility_towards(Direction::Left, x, y), map.visibility_left(x, y));
+            assert_eq!(visibility.visibility_towards(Direction::Right, x, y), map.visibility_right(x, y));
+            assert_eq!(visibility.visibility_towards(Direction::Up, x, y), map.visibility_up(x, y));
+            assert_eq!(visibility.visibility_towards(Direction::Down, x, y), map.visibility_down(x, y));
+            assert_eq!(visibility.scenic_score(x, y), map.scenic_score(x, y));
+        }
+    }
+
+    let map: Map = "1".parse()?;
+    assert_eq!(map.count_visible(), 1);
+    assert_eq!(map.highest_scenic_score(), (0, 0, 0));
+
+    Ok(())
+}
+
+#[test]
+fn test_analytics() -> Result<(), Error> {
+    let map: Map = EXAMPLE.parse()?;
+    let observer = Observer { x: 2, y: 2, height: 4 };
+    assert_eq!(map.visible_from(&observer, false), vec![(1, 2), (2, 1), (2, 3), (3, 2), (4, 2)]);
+    let expected = vec![
+        (0, 4),
+        (1, 1),
+        (1, 2),
+        (1, 3),
+        (2, 1),
+        (2, 3),
+        (3, 1),
+        (3, 2),
+        (3, 3),
+        (4, 0),
+        (4, 2),
+    ];
+    assert_eq!(map.visible_from(&observer, true), expected);
+    assert_eq!(
+        map.visible_from(&Observer { x: 0, y: 0, height: 0 }, true),
+        vec![(0, 1), (1, 0), (1, 1)]
+    );
+
+    assert_eq!(map.visibility().top_scenic_scores(3), vec![(8, 2, 3), (6, 1, 2), (4, 2, 1)]);
+    assert_eq!(map.visibility().scenic_heatmap(), "     \n .=. \n *.: \n .@- \n     \n");
+
+    let map: Map = std::fs::read_to_string("input/day8")?.parse()?;
+    let visibility = map.visibility();
+    let top = visibility.top_scenic_scores(10);
+    assert_eq!(top.len(), 10);
+    assert_eq!(top[0].0, 392080);
+    assert!(top.windows(2).all(|w| w[0].0 >= w[1].0));
+
+    Ok(())
+}
@@ -3,15 +3,12 @@
 // part2: (10 knots on rope) How many positions does the tail of the rope visit at least once?
 
 use crate::error::Error;
+use crate::utils::render_grid;
 use std::collections::HashSet;
 use std::str::FromStr;
 
 type Pos = (i64, i64);
 
-pub fn too_long(lhs: &Pos, rhs: &Pos) -> bool {
-    (lhs.0 - rhs.0).abs() > 1 || (lhs.1 - rhs.1).abs() > 1
-}
-
 pub fn move_diagonally_towards(from: &Pos, towards: &Pos) -> Pos {
     (from.0 + (towards.0 - from.0).signum(), from.1 + (towards.1 - from.1).signum())
 }
@@ -57,58 +54,167 @@ pub struct Instructions {
     instructions: Vec<Instruction>,
 }
 
-#[derive(Debug)]
-pub struct State {
-    knots: Vec<Pos>,
-    tails_visited: HashSet<Pos>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUp {
+    // a lagging knot moves along one axis at a time
+    Straight,
+    // a lagging knot moves diagonally when it is not in the same row or column
+    Diagonal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RopeRules {
+    pub num_knots: usize,
+    // how far (in both x and y) a knot may be from the knot in front of it before it moves
+    pub max_slack: i64,
+    pub catch_up: CatchUp,
 }
 
-impl State {
+impl RopeRules {
     pub fn new(num_knots: usize) -> Self {
         Self {
-            knots: vec![Pos::default(); num_knots],
-            tails_visited: HashSet::from([Pos::default()]),
+            num_knots,
+            max_slack: 1,
+            catch_up: CatchUp::Diagonal,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Rope {
+    rules: RopeRules,
+    // position of every knot after every step of the head, starting with the initial position
+    trajectories: Vec<Vec<Pos>>,
+    visited: Vec<HashSet<Pos>>,
+}
+
+impl Rope {
+    pub fn new(rules: RopeRules) -> Result<Self, Error> {
+        if rules.num_knots == 0 || rules.max_slack < 0 {
+            return Err(Error::General(format!("invalid rope rules: {:?}", rules)));
+        }
+        Ok(Self {
+            rules,
+            trajectories: vec![vec![Pos::default()]; rules.num_knots],
+            visited: vec![HashSet::from([Pos::default()]); rules.num_knots],
+        })
+    }
+
+    pub fn rules(&self) -> &RopeRules {
+        &self.rules
+    }
+
+    pub fn knot(&self, index: usize) -> Pos {
+        *self.trajectories[index].last().unwrap()
+    }
+
+    pub fn knots(&self) -> Vec<Pos> {
+        (0..self.rules.num_knots).map(|index| self.knot(index)).collect()
+    }
+
+    pub fn tail(&self) -> Pos {
+        self.knot(self.rules.num_knots - 1)
+    }
+
+    pub fn trajectory(&self, index: usize) -> &[Pos] {
+        &self.trajectories[index]
+    }
+
+    pub fn visited(&self, index: usize) -> &HashSet<Pos> {
+        &self.visited[index]
+    }
+
+    pub fn tail_visited(&self) -> &HashSet<Pos> {
+        &self.visited[self.rules.num_knots - 1]
+    }
+
+    pub fn num_steps(&self) -> usize {
+        self.trajectories[0].len() - 1
+    }
+
+    fn follow(&self, knot: Pos, leader: Pos) -> Pos {
+        let mut knot = knot;
+        while (leader.0 - knot.0).abs() > self.rules.max_slack || (leader.1 - knot.1).abs() > self.rules.max_slack {
+            knot = match self.rules.catch_up {
+                CatchUp::Diagonal => move_diagonally_towards(&knot, &leader),
+                CatchUp::Straight if (leader.0 - knot.0).abs() >= (leader.1 - knot.1).abs() => {
+                    (knot.0 + (leader.0 - knot.0).signum(), knot.1)
+                }
+                CatchUp::Straight => (knot.0, knot.1 + (leader.1 - knot.1).signum()),
+            };
         }
+        knot
     }
 
     pub fn apply(&mut self, i: &Instruction) -> Result<(), Error> {
-        let no_knots_error = || Error::General("no knots".to_string());
         for _ in 0..i.amount {
-            let head = self.knots.first_mut().ok_or_else(no_knots_error)?;
+            let mut leader = self.knot(0);
             match i.direction {
                 Direction::UP => {
-                    head.1 -= 1;
+                    leader.1 -= 1;
                 }
                 Direction::RIGHT => {
-                    head.0 += 1;
+                    leader.0 += 1;
                 }
                 Direction::DOWN => {
-                    head.1 += 1;
+                    leader.1 += 1;
                 }
                 Direction::LEFT => {
-                    head.0 -= 1;
+                    leader.0 -= 1;
                 }
             }
-            for index in 1..self.knots.len() {
-                if too_long(&self.knots[index], &self.knots[index - 1]) {
-                    self.knots[index] = move_diagonally_towards(&self.knots[index], &self.knots[index - 1]);
-                    if index == self.knots.len() - 1 {
-                        self.tails_visited.insert(*self.knots.last().ok_or_else(no_knots_error)?);
-                    }
+            for index in 0..self.rules.num_knots {
+                if index > 0 {
+                    leader = self.follow(self.knot(index), leader);
                 }
+                self.trajectories[index].push(leader);
+                self.visited[index].insert(leader);
             }
         }
         Ok(())
     }
+
+    // knot positions after every step, starting with the initial position
+    pub fn frames(&self) -> impl Iterator<Item = Vec<Pos>> + '_ {
+        (0..=self.num_steps()).map(move |step| self.trajectories.iter().map(|trajectory| trajectory[step]).collect())
+    }
+
+    // smallest area containing every position any knot has been at, as (min, max)
+    pub fn bounds(&self) -> (Pos, Pos) {
+        let positions = self.trajectories.iter().flatten();
+        let min = positions.clone().fold((0, 0), |min: Pos, p| (min.0.min(p.0), min.1.min(p.1)));
+        let max = positions.fold((0, 0), |max: Pos, p| (max.0.max(p.0), max.1.max(p.1)));
+        (min, max)
+    }
+
+    // draws a frame like the puzzle does, using the bounds of the whole simulation
+    pub fn render(&self, knots: &[Pos]) -> String {
+        let (min, max) = self.bounds();
+        render_grid((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize, |x, y| {
+            let pos = (x as i64 + min.0, y as i64 + min.1);
+            match knots.iter().position(|&knot| knot == pos) {
+                Some(0) => 'H',
+                Some(1) if knots.len() == 2 => 'T',
+                Some(index) if index < 10 => char::from_digit(index as u32, 10).unwrap(),
+                Some(_) => '#',
+                None if pos == Pos::default() => 's',
+                None => '.',
+            }
+        })
+    }
 }
 
 impl Instructions {
-    pub fn follow(&self, num_knots: usize) -> Result<State, Error> {
-        let mut state = State::new(num_knots);
+    pub fn follow(&self, num_knots: usize) -> Result<Rope, Error> {
+        self.simulate(RopeRules::new(num_knots))
+    }
+
+    pub fn simulate(&self, rules: RopeRules) -> Result<Rope, Error> {
+        let mut rope = Rope::new(rules)?;
         for instruction in self.instructions.iter() {
-            state.apply(instruction)?;
+            rope.apply(instruction)?;
         }
-        Ok(state)
+        Ok(rope)
     }
 }
 
@@ -154,9 +260,9 @@ R 2"#;
         }
     );
     let result = instructions.follow(2)?;
-    assert_eq!(result.tails_visited.len(), 13);
+    assert_eq!(result.tail_visited().len(), 13);
     let result = instructions.follow(10)?;
-    assert_eq!(result.tails_visited.len(), 1);
+    assert_eq!(result.tail_visited().len(), 1);
 
     let input = r#"
 R 5
@@ -169,13 +275,67 @@ L 25
 U 20"#;
     let instructions: Instructions = input.parse()?;
     let result = instructions.follow(10)?;
-    assert_eq!(result.tails_visited.len(), 36);
+    assert_eq!(result.tail_visited().len(), 36);
 
     let instructions: Instructions = std::fs::read_to_string("input/day9")?.parse()?;
     let result = instructions.follow(2)?;
-    assert_eq!(result.tails_visited.len(), 6470);
+    assert_eq!(result.tail_visited().len(), 6470);
     let result = instructions.follow(10)?;
-    assert_eq!(result.tails_visited.len(), 2658);
+    assert_eq!(result.tail_visited().len(), 2658);
+
+    Ok(())
+}
+
+#[test]
+fn test_rope() -> Result<(), Error> {
+    let input = r#"
+R 4
+U 4
+L 3
+D 1
+R 4
+D 1
+L 5
+R 2"#;
+    let instructions: Instructions = input.parse()?;
+    let rope = instructions.follow(2)?;
+    assert_eq!(rope.num_steps(), 24);
+    assert_eq!(rope.trajectory(0).len(), 25);
+    assert_eq!(rope.knots(), vec![(2, -2), (1, -2)]);
+    assert_eq!(rope.frames().count(), 25);
+    assert_eq!(
+        rope.render(&rope.frames().nth(2).unwrap()),
+        "......\n......\n......\n......\nsTH...\n"
+    );
+    assert_eq!(rope.render(&rope.knots()), "......\n......\n.TH...\n......\ns.....\n");
+
+    let rope = instructions.follow(10)?;
+    assert_eq!(
+        rope.render(&rope.frames().nth(8).unwrap()),
+        "....H.\n....1.\n..432.\n.5....\n6.....\n"
+    );
+
+    let instructions: Instructions = std::fs::read_to_string("input/day9")?.parse()?;
+    let rope = instructions.follow(10)?;
+    for knot in 1..10 {
+        assert_eq!(rope.visited(knot).len(), instructions.follow(knot + 1)?.tail_visited().len());
+    }
+
+    let mut rules = RopeRules::new(3);
+    rules.catch_up = CatchUp::Straight;
+    let rope = "R 3\nU 2".parse::<Instructions>()?.simulate(rules)?;
+    assert_eq!(rope.knots(), vec![(3, -2), (2, -1), (1, 0)]);
+    assert!(rope
+        .frames()
+        .flat_map(|knots| knots.windows(2).map(|w| w[0].0 - w[1].0).collect::<Vec<_>>())
+        .all(|dx| dx.abs() <= 1));
+
+    let mut rules = RopeRules::new(2);
+    rules.max_slack = 2;
+    let rope = "R 5".parse::<Instructions>()?.simulate(rules)?;
+    assert_eq!(rope.tail(), (3, 0));
+    assert_eq!(rope.tail_visited().len(), 4);
+    assert!(Rope::new(RopeRules::new(0)).is_err());
 
     Ok(())
 }
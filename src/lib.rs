@@ -1,6 +1,7 @@
 extern crate core;
 
 pub mod error;
+pub mod utils;
 
 // pub mod day1;
 // pub mod day10;
@@ -1,3 +1,14 @@
-use crate::error;
-
-
+// renders a width x height grid row by row, one character per cell
+pub fn render_grid<F>(width: usize, height: usize, cell: F) -> String
+where
+    F: Fn(usize, usize) -> char,
+{
+    let mut output = String::with_capacity((width + 1) * height);
+    for y in 0..height {
+        for x in 0..width {
+            output.push(cell(x, y));
+        }
+        output.push('\n');
+    }
+    output
+}